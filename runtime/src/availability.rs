@@ -0,0 +1,343 @@
+//! # Availability Module
+//!
+//! Publishers store metadata on the chain, the Availability module adds whether the content can actually be retrieved.
+//! Active staked pinning providers of the Providers module attest that they pin the content of a DID until an expiry block.
+//! Anyone can report a DID as unavailable for a small fee, reports count for one week.
+//! The availability score of a DID is the share of active attestations among attestations and recent reports.
+
+use parity_codec::{Decode, Encode};
+use rstd::vec::Vec;
+use runtime_primitives::traits::As;
+use support::{decl_event, decl_module, decl_storage, dispatch::Result, ensure, StorageMap};
+use system::ensure_signed;
+
+use crate::{metalog, providers, treasury};
+
+const ERR_DID_NOT_EXIST: &str = "This DID does not exist";
+
+const ERR_PROVIDER_NOT_ACTIVE: &str = "This account is no active pinning provider";
+
+const ERR_ATTESTATION_NOT_EXIST: &str = "You haven't attested this DID";
+const ERR_ATTESTATION_EXPIRY: &str = "The expiry needs to be in the future and within the maximum attestation period";
+const ERR_ATTESTERS_LIMIT: &str = "This DID has reached the maximum number of attestations";
+
+const ERR_REPORT_EXIST: &str = "You already reported this DID as unavailable";
+const ERR_REPORTS_LIMIT: &str = "This DID has reached the maximum number of unavailability reports";
+
+const ERR_BYTEARRAY_LIMIT_CID: &str = "CID bytearray is too large";
+const ERR_BYTEARRAY_LIMIT_LOCATION: &str = "Location bytearray is too large";
+
+const BYTEARRAY_LIMIT_CID: usize = 100;
+const BYTEARRAY_LIMIT_LOCATION: usize = 200;
+
+/// Attestations can be given for at most one year with 6 seconds blocktime
+const MAX_ATTESTATION_PERIOD: u64 = 5256000;
+/// Reports count for one week with 6 seconds blocktime
+const REPORT_PERIOD: u64 = 100800;
+/// Fee of an unavailability report, goes to the treasury
+const REPORT_FEE: u64 = 10;
+
+/// Limits the work done per DID
+const MAX_ATTESTERS: usize = 20;
+const MAX_REPORTS: usize = 50;
+
+/// The module's configuration trait.
+pub trait Trait: metalog::Trait {
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+/// Statement of a pinning provider, that it keeps the content of a DID available
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Attestation<BlockNumber> {
+    /// Pinned CID
+    pub cid: Vec<u8>,
+    /// Multiaddr the content can be retrieved from
+    pub location: Vec<u8>,
+    /// Block number at which the attestation ends
+    pub expiry: BlockNumber,
+}
+
+/// Report that the content of a DID couldn't be retrieved
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Report<AccountId, BlockNumber> {
+    pub reporter: AccountId,
+    pub reported_at: BlockNumber,
+}
+
+/// Availability of a DID
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct AvailabilityScore {
+    /// Active attestations of active providers
+    pub pinned: u32,
+    /// Unavailability reports of the last week
+    pub reports: u32,
+    /// Share of attestations in percent, 0 without any attestation
+    pub score: u8,
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Availability {
+        /// Attestations by (DID, provider)
+        Attestations get(attestation_of): map (Vec<u8>, T::AccountId) => Option<Attestation<T::BlockNumber>>;
+        /// Providers, which attested a DID
+        Attesters get(attesters_of): map Vec<u8> => Vec<T::AccountId>;
+
+        /// Unavailability reports of a DID
+        Reports get(reports_of): map Vec<u8> => Vec<Report<T::AccountId, T::BlockNumber>>;
+    }
+}
+
+decl_module! {
+    /// The module declaration.
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        fn deposit_event<T>() = default;
+
+        /// Attest that the content of a DID is pinned until the expiry, replaces an earlier attestation
+        fn attest(origin, did: Vec<u8>, cid: Vec<u8>, location: Vec<u8>, expiry: T::BlockNumber) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(Self::is_provider(&sender), ERR_PROVIDER_NOT_ACTIVE);
+            ensure!(cid.len() <= BYTEARRAY_LIMIT_CID, ERR_BYTEARRAY_LIMIT_CID);
+            ensure!(location.len() <= BYTEARRAY_LIMIT_LOCATION, ERR_BYTEARRAY_LIMIT_LOCATION);
+            ensure!(<metalog::Module<T>>::metalog_by_did(&did).is_some(), ERR_DID_NOT_EXIST);
+            let block_number = <system::Module<T>>::block_number();
+            ensure!(
+                expiry > block_number && expiry <= block_number + T::BlockNumber::sa(MAX_ATTESTATION_PERIOD),
+                ERR_ATTESTATION_EXPIRY
+            );
+
+            let attesters = Self::attesters_of(&did);
+            if !attesters.contains(&sender) {
+                // expired attestations make room for new ones
+                let (mut active, expired): (Vec<_>, Vec<_>) = attesters.into_iter().partition(|provider| {
+                    match Self::attestation_of((did.clone(), provider.clone())) {
+                        Some(attestation) => attestation.expiry > block_number,
+                        None => false,
+                    }
+                });
+                ensure!(active.len() < MAX_ATTESTERS, ERR_ATTESTERS_LIMIT);
+
+                for provider in expired {
+                    <Attestations<T>>::remove((did.clone(), provider));
+                }
+                active.push(sender.clone());
+                <Attesters<T>>::insert(&did, active);
+            }
+            <Attestations<T>>::insert((did.clone(), sender.clone()), Attestation { cid: cid.clone(), location, expiry });
+
+            Self::deposit_event(RawEvent::Attested(sender, did, cid, expiry));
+            Ok(())
+        }
+
+        /// Withdraw an attestation before its expiry
+        fn revoke_attestation(origin, did: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            <Attestations<T>>::take((did.clone(), sender.clone())).ok_or(ERR_ATTESTATION_NOT_EXIST)?;
+            <Attesters<T>>::mutate(&did, |attesters| attesters.retain(|provider| provider != &sender));
+
+            Self::deposit_event(RawEvent::AttestationRevoked(sender, did));
+            Ok(())
+        }
+
+        /// Report that the content of a DID is unavailable, the fee goes to the treasury
+        fn report_unavailable(origin, did: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(<metalog::Module<T>>::metalog_by_did(&did).is_some(), ERR_DID_NOT_EXIST);
+            let mut reports = Self::_recent_reports(&did);
+            ensure!(!reports.iter().any(|report| report.reporter == sender), ERR_REPORT_EXIST);
+            ensure!(reports.len() < MAX_REPORTS, ERR_REPORTS_LIMIT);
+
+            <treasury::Module<T>>::collect_fee(&sender, T::Balance::sa(REPORT_FEE))?;
+
+            reports.push(Report {
+                reporter: sender.clone(),
+                reported_at: <system::Module<T>>::block_number(),
+            });
+            <Reports<T>>::insert(&did, reports);
+
+            Self::deposit_event(RawEvent::UnavailabilityReported(sender, did));
+            Ok(())
+        }
+    }
+}
+
+decl_event!(
+    pub enum Event<T> where
+        <T as system::Trait>::AccountId,
+        <T as system::Trait>::BlockNumber
+    {
+        /// provider, did, cid, expiry
+        Attested(AccountId, Vec<u8>, Vec<u8>, BlockNumber),
+        /// provider, did
+        AttestationRevoked(AccountId, Vec<u8>),
+        /// reporter, did
+        UnavailabilityReported(AccountId, Vec<u8>),
+    }
+);
+
+impl<T: Trait> Module<T> {
+    /// Whether the account has registered a pinning provider, which is active in the Providers module
+    pub fn is_provider(account: &T::AccountId) -> bool {
+        match <providers::Module<T>>::provider_of(account) {
+            Some(id) => <providers::Module<T>>::is_active(id),
+            None => false,
+        }
+    }
+
+    /// Availability of a DID at the current block
+    pub fn availability_of(did: &Vec<u8>) -> AvailabilityScore {
+        let block_number = <system::Module<T>>::block_number();
+        let pinned = Self::attesters_of(did)
+            .into_iter()
+            .filter(|provider| Self::is_provider(provider))
+            .filter(|provider| match Self::attestation_of((did.clone(), provider.clone())) {
+                Some(attestation) => attestation.expiry > block_number,
+                None => false,
+            })
+            .count() as u32;
+        let reports = Self::_recent_reports(did).len() as u32;
+
+        let score = if pinned == 0 {
+            0
+        } else {
+            (pinned * 100 / (pinned + reports)) as u8
+        };
+        AvailabilityScore { pinned, reports, score }
+    }
+
+    /// Attestations of a DID by provider, including expired ones
+    pub fn attestations_of(did: &Vec<u8>) -> Vec<(T::AccountId, Attestation<T::BlockNumber>)> {
+        Self::attesters_of(did)
+            .into_iter()
+            .filter_map(|provider| {
+                Self::attestation_of((did.clone(), provider.clone())).map(|attestation| (provider, attestation))
+            })
+            .collect()
+    }
+
+    /// Reports filed within the report period
+    fn _recent_reports(did: &Vec<u8>) -> Vec<Report<T::AccountId, T::BlockNumber>> {
+        let block_number = <system::Module<T>>::block_number();
+        let mut reports = Self::reports_of(did);
+        reports.retain(|report| report.reported_at + T::BlockNumber::sa(REPORT_PERIOD) > block_number);
+        reports
+    }
+}
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock::{create, new_test_ext, Origin, Test};
+    use runtime_io::with_externalities;
+    use support::{assert_noop, assert_ok};
+
+    type Balances = balances::Module<Test>;
+    type System = system::Module<Test>;
+    type Treasury = treasury::Module<Test>;
+    type Providers = providers::Module<Test>;
+    type Availability = Module<Test>;
+
+    const STAKE: u64 = 10000;
+
+    /// Registers a pinning provider with the minimum stake
+    fn register(account: u64) {
+        let _ = Balances::make_free_balance_be(&account, STAKE);
+        assert_ok!(Providers::register(Origin::signed(account), vec![1], 5, STAKE));
+    }
+
+    #[test]
+    fn is_provider_works() {
+        with_externalities(&mut new_test_ext(), || {
+            assert!(!Availability::is_provider(&2));
+            register(2);
+            assert!(Availability::is_provider(&2));
+
+            // unbonding providers are no longer active
+            assert_ok!(Providers::unregister(Origin::signed(2)));
+            assert!(!Availability::is_provider(&2));
+        });
+    }
+
+    #[test]
+    fn attest_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            let location = b"/ip4/127.0.0.1/tcp/4001".to_vec();
+            assert_noop!(
+                Availability::attest(Origin::signed(2), did.clone(), vec![7], location.clone(), 10),
+                ERR_PROVIDER_NOT_ACTIVE
+            );
+            register(2);
+            register(3);
+            assert_noop!(
+                Availability::attest(Origin::signed(2), did.clone(), vec![7], location.clone(), 10),
+                ERR_DID_NOT_EXIST
+            );
+            create(1, did.clone());
+            assert_noop!(
+                Availability::attest(Origin::signed(2), did.clone(), vec![7], location.clone(), 0),
+                ERR_ATTESTATION_EXPIRY
+            );
+            assert_noop!(
+                Availability::attest(Origin::signed(2), did.clone(), vec![7], location.clone(), MAX_ATTESTATION_PERIOD + 1),
+                ERR_ATTESTATION_EXPIRY
+            );
+
+            assert_ok!(Availability::attest(Origin::signed(2), did.clone(), vec![7], location.clone(), 10));
+            assert_ok!(Availability::attest(Origin::signed(2), did.clone(), vec![7], location.clone(), 20));
+            assert_ok!(Availability::attest(Origin::signed(3), did.clone(), vec![7], location.clone(), 10));
+            assert_eq!(Availability::attesters_of(&did), vec![2, 3]);
+            assert_eq!(Availability::attestation_of((did.clone(), 2)).unwrap().expiry, 20);
+            assert_eq!(Availability::attestations_of(&did).len(), 2);
+            assert_eq!(Availability::availability_of(&did).pinned, 2);
+
+            // expired attestations and unregistered providers don't count
+            System::set_block_number(10);
+            assert_eq!(Availability::availability_of(&did).pinned, 1);
+            assert_ok!(Providers::unregister(Origin::signed(2)));
+            assert_eq!(Availability::availability_of(&did).pinned, 0);
+
+            assert_noop!(Availability::revoke_attestation(Origin::signed(4), did.clone()), ERR_ATTESTATION_NOT_EXIST);
+            assert_ok!(Availability::revoke_attestation(Origin::signed(3), did.clone()));
+            assert_eq!(Availability::attesters_of(&did), vec![2]);
+            assert_eq!(Availability::attestation_of((did.clone(), 3)), None);
+        });
+    }
+
+    #[test]
+    fn report_unavailable_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            assert_noop!(Availability::report_unavailable(Origin::signed(5), did.clone()), ERR_DID_NOT_EXIST);
+            create(1, did.clone());
+            assert!(Availability::report_unavailable(Origin::signed(5), did.clone()).is_err());
+
+            let _ = Balances::make_free_balance_be(&5, 100);
+            let _ = Balances::make_free_balance_be(&6, 100);
+            assert_ok!(Availability::report_unavailable(Origin::signed(5), did.clone()));
+            System::set_block_number(100);
+            assert_noop!(Availability::report_unavailable(Origin::signed(5), did.clone()), ERR_REPORT_EXIST);
+            assert_eq!(Balances::free_balance(&5), 100 - REPORT_FEE);
+            assert_eq!(Treasury::pot(), REPORT_FEE);
+
+            register(2);
+            assert_ok!(Availability::attest(Origin::signed(2), did.clone(), vec![7], vec![8], 1000));
+            assert_eq!(Availability::availability_of(&did), AvailabilityScore { pinned: 1, reports: 1, score: 50 });
+            assert_ok!(Availability::report_unavailable(Origin::signed(6), did.clone()));
+            assert_eq!(Availability::availability_of(&did).score, 33);
+
+            // reports only count for the report period
+            System::set_block_number(REPORT_PERIOD + 1);
+            assert_eq!(Availability::availability_of(&did).reports, 1);
+            assert_ok!(Availability::report_unavailable(Origin::signed(5), did.clone()));
+            assert_eq!(Availability::reports_of(&did).len(), 2);
+        });
+    }
+}
@@ -0,0 +1,569 @@
+//! # Deals Module
+//!
+//! The Deals module lets owners of a DID pay a registered pinning provider to keep its content available for a number of blocks.
+//! The payment for the whole duration is reserved from the publisher's balance when the deal is offered.
+//! Offers, which the provider doesn't accept within a day, expire and the payment is returned.
+//! Once the provider accepted, the payment streams to the provider per block, the provider claims what has accrued.
+//! A deal ends when it expires, when one of both parties cancels it, when the DID is deleted
+//! or when root or higher federation ranks confirm an unavailability report.
+//! The provider is paid until the end, respectively until the first confirmed report, as far as the reserve of the publisher covers it.
+//! The rest is returned to the publisher.
+
+use parity_codec::{Decode, Encode};
+use rstd::vec::Vec;
+use runtime_primitives::traits::{As, CheckedMul};
+use support::{
+    decl_event, decl_module, decl_storage,
+    dispatch::Result,
+    ensure,
+    traits::ReservableCurrency,
+    StorageMap, StorageValue,
+};
+use system::ensure_signed;
+
+use crate::{availability, federation, metalog, providers};
+
+const ERR_PROVIDER_NOT_ACTIVE: &str = "This pinning provider is not active";
+const ERR_PROVIDER_NOT_EXIST: &str = "This pinning provider does not exist";
+
+const ERR_DEAL_NOT_EXIST: &str = "This deal does not exist";
+const ERR_DEAL_STARTED: &str = "This deal has already been accepted";
+const ERR_DEAL_NOT_STARTED: &str = "This deal hasn't been accepted yet";
+const ERR_DEAL_DURATION: &str = "The duration needs to be above zero and within the maximum deal period";
+const ERR_PRICE_ZERO: &str = "The price per block needs to be above zero";
+const ERR_DEALS_LIMIT: &str = "This DID has reached the maximum number of deals";
+
+const ERR_NOT_PROVIDER: &str = "You are not the provider of this deal";
+const ERR_NOT_PARTY: &str = "You are neither the publisher nor the provider of this deal";
+const ERR_REPORT_NOT_EXIST: &str = "This DID hasn't been reported as unavailable during the deal";
+
+const ERR_OVERFLOW: &str = "Overflow adding a new deal";
+
+/// Minimum federation rank to confirm unavailability reports
+const CONFIRM_RANK: u16 = federation::CAPTAIN_RANK;
+
+/// Deals last at most one year with 6 seconds blocktime
+const MAX_DEAL_PERIOD: u64 = 5256000;
+/// Offers expire after one day with 6 seconds blocktime
+const OFFER_PERIOD: u64 = 14400;
+/// Limits the work done per DID
+const MAX_DEALS_PER_DID: usize = 20;
+
+/// The module's configuration trait.
+pub trait Trait: availability::Trait {
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+/// Pinning deal between a publisher and a provider
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Deal<AccountId, Balance, BlockNumber> {
+    /// Pinned DID
+    pub did: Vec<u8>,
+    /// Account, which pays for the deal
+    pub publisher: AccountId,
+    /// ID of the provider in the provider registry
+    pub provider: u64,
+    /// Payment per block
+    pub price_per_block: Balance,
+    /// Number of blocks the content is pinned
+    pub duration: BlockNumber,
+    /// Block at which the provider accepted, none before
+    pub start: Option<BlockNumber>,
+    /// Block until which the provider has been paid
+    pub paid_until: BlockNumber,
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Deals {
+        /// Deals by ID
+        Deals get(deal): map u64 => Option<Deal<T::AccountId, T::Balance, T::BlockNumber>>;
+        /// Number of created deals, the ID of the next deal
+        DealCount get(deal_count): u64;
+
+        /// Open deals of a DID
+        DealsOfDid get(deals_of_did): map Vec<u8> => Vec<u64>;
+        /// Accepted deals by the block at which they end, offers by the block at which they expire
+        DealExpiry get(deals_ending_at): map T::BlockNumber => Vec<u64>;
+    }
+}
+
+decl_module! {
+    /// The module declaration.
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        fn deposit_event<T>() = default;
+
+        /// Ends the deals and offers, which expire at this block
+        fn on_finalize(n: T::BlockNumber) {
+            for id in <DealExpiry<T>>::take(n) {
+                if let Some(deal) = Self::deal(id) {
+                    match Self::_end_of(&deal) {
+                        // the offer hasn't been accepted in time
+                        None => {
+                            Self::_close(id, deal, n);
+                            Self::deposit_event(RawEvent::OfferExpired(id));
+                        }
+                        Some(end) if end == n => {
+                            Self::_close(id, deal, n);
+                            Self::deposit_event(RawEvent::DealExpired(id));
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+
+        /// Offer a deal to pin the content of an owned DID, the payment for the whole duration is reserved
+        pub fn create_deal(origin, did: Vec<u8>, provider: u64, price_per_block: T::Balance, duration: T::BlockNumber) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            <metalog::Module<T>>::check_ownership(&sender, &did)?;
+            ensure!(<providers::Module<T>>::is_active(provider), ERR_PROVIDER_NOT_ACTIVE);
+            ensure!(price_per_block > T::Balance::sa(0), ERR_PRICE_ZERO);
+            ensure!(
+                duration > T::BlockNumber::sa(0) && duration <= T::BlockNumber::sa(MAX_DEAL_PERIOD),
+                ERR_DEAL_DURATION
+            );
+            let mut deals = Self::deals_of_did(&did);
+            ensure!(deals.len() < MAX_DEALS_PER_DID, ERR_DEALS_LIMIT);
+            let total = price_per_block.checked_mul(&T::Balance::sa(duration.as_())).ok_or(ERR_OVERFLOW)?;
+            let id = Self::deal_count();
+            let updated_count = id.checked_add(1).ok_or(ERR_OVERFLOW)?;
+
+            <balances::Module<T> as ReservableCurrency<_>>::reserve(&sender, total)?;
+
+            let deal = Deal {
+                did: did.clone(),
+                publisher: sender.clone(),
+                provider,
+                price_per_block,
+                duration,
+                start: None,
+                paid_until: T::BlockNumber::sa(0),
+            };
+            <Deals<T>>::insert(id, deal);
+            <DealCount<T>>::put(updated_count);
+            deals.push(id);
+            <DealsOfDid<T>>::insert(&did, deals);
+            let offer_end = <system::Module<T>>::block_number() + T::BlockNumber::sa(OFFER_PERIOD);
+            <DealExpiry<T>>::mutate(offer_end, |deals| deals.push(id));
+
+            Self::deposit_event(RawEvent::DealCreated(id, sender, did, provider, total));
+            Ok(())
+        }
+
+        /// Accept a deal as its provider, the payment streams from this block on
+        pub fn accept_deal(origin, id: u64) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let mut deal = Self::deal(id).ok_or(ERR_DEAL_NOT_EXIST)?;
+            ensure!(Self::_is_provider_of(&sender, &deal), ERR_NOT_PROVIDER);
+            ensure!(deal.start.is_none(), ERR_DEAL_STARTED);
+            ensure!(<providers::Module<T>>::is_active(deal.provider), ERR_PROVIDER_NOT_ACTIVE);
+
+            let block_number = <system::Module<T>>::block_number();
+            let end = block_number + deal.duration;
+            deal.start = Some(block_number);
+            deal.paid_until = block_number;
+            <Deals<T>>::insert(id, deal);
+            <DealExpiry<T>>::mutate(end, |deals| deals.push(id));
+
+            Self::deposit_event(RawEvent::DealAccepted(id, end));
+            Ok(())
+        }
+
+        /// Claim the payment, which has accrued since the last claim
+        pub fn claim(origin, id: u64) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let mut deal = Self::deal(id).ok_or(ERR_DEAL_NOT_EXIST)?;
+            ensure!(Self::_is_provider_of(&sender, &deal), ERR_NOT_PROVIDER);
+            let end = Self::_end_of(&deal).ok_or(ERR_DEAL_NOT_STARTED)?;
+
+            let block_number = <system::Module<T>>::block_number();
+            let until = if block_number < end { block_number } else { end };
+            let paid = Self::_pay(&deal, until);
+            deal.paid_until = until;
+            <Deals<T>>::insert(id, deal);
+
+            Self::deposit_event(RawEvent::Claimed(id, paid));
+            Ok(())
+        }
+
+        /// Cancel a deal as publisher or provider, the provider is paid until this block
+        pub fn cancel_deal(origin, id: u64) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let deal = Self::deal(id).ok_or(ERR_DEAL_NOT_EXIST)?;
+            ensure!(sender == deal.publisher || Self::_is_provider_of(&sender, &deal), ERR_NOT_PARTY);
+
+            let block_number = <system::Module<T>>::block_number();
+            Self::_close(id, deal, block_number);
+
+            Self::deposit_event(RawEvent::DealCanceled(id, sender));
+            Ok(())
+        }
+
+        /// Confirm that the content of an accepted deal has been unavailable, only root or higher federation ranks.
+        /// The provider is paid until the first unavailability report filed during the deal.
+        pub fn confirm_unavailable(origin, id: u64) -> Result {
+            <federation::Module<T>>::ensure_root_or_rank(origin, CONFIRM_RANK)?;
+
+            let deal = Self::deal(id).ok_or(ERR_DEAL_NOT_EXIST)?;
+            let start = deal.start.ok_or(ERR_DEAL_NOT_STARTED)?;
+            let reported_at = <availability::Module<T>>::reports_of(&deal.did)
+                .into_iter()
+                .map(|report| report.reported_at)
+                .filter(|reported_at| *reported_at >= start)
+                .min()
+                .ok_or(ERR_REPORT_NOT_EXIST)?;
+
+            // the provider keeps what it has already claimed
+            let until = if reported_at > deal.paid_until { reported_at } else { deal.paid_until };
+            Self::_close(id, deal, until);
+
+            Self::deposit_event(RawEvent::DealFailed(id, reported_at));
+            Ok(())
+        }
+    }
+}
+
+decl_event!(
+    pub enum Event<T> where
+        <T as system::Trait>::AccountId,
+        <T as system::Trait>::BlockNumber,
+        <T as balances::Trait>::Balance
+    {
+        /// deal id, publisher, did, provider id, reserved payment
+        DealCreated(u64, AccountId, Vec<u8>, u64, Balance),
+        /// deal id, end of the deal
+        DealAccepted(u64, BlockNumber),
+        /// deal id, payment
+        Claimed(u64, Balance),
+        /// deal id, payment when the deal ended
+        Paid(u64, Balance),
+        /// deal id, canceling party
+        DealCanceled(u64, AccountId),
+        DealExpired(u64),
+        /// deal id of an offer, which hasn't been accepted in time
+        OfferExpired(u64),
+        /// deal id, ended by a delete request of the DID
+        DealEndedByDeletion(u64),
+        /// deal id, block of the confirmed unavailability report
+        DealFailed(u64, BlockNumber),
+    }
+);
+
+impl<T: Trait> Module<T> {
+    /// Open deals of a DID
+    pub fn deals_of(did: &Vec<u8>) -> Vec<(u64, Deal<T::AccountId, T::Balance, T::BlockNumber>)> {
+        Self::deals_of_did(did)
+            .into_iter()
+            .filter_map(|id| Self::deal(id).map(|deal| (id, deal)))
+            .collect()
+    }
+
+    /// Block at which an accepted deal ends
+    fn _end_of(deal: &Deal<T::AccountId, T::Balance, T::BlockNumber>) -> Option<T::BlockNumber> {
+        deal.start.map(|start| start + deal.duration)
+    }
+
+    fn _is_provider_of(account: &T::AccountId, deal: &Deal<T::AccountId, T::Balance, T::BlockNumber>) -> bool {
+        match <providers::Module<T>>::provider(deal.provider) {
+            Some(provider) => &provider.account == account,
+            None => false,
+        }
+    }
+
+    /// Payment for the blocks
+    fn _cost(deal: &Deal<T::AccountId, T::Balance, T::BlockNumber>, blocks: T::BlockNumber) -> T::Balance {
+        // can't overflow, the payment for the whole duration has been checked on creation
+        deal.price_per_block * T::Balance::sa(blocks.as_())
+    }
+
+    /// Moves the payment since the last payment from the publisher's reserve to the provider and returns the paid amount.
+    /// Less is paid, if the reserve of the publisher has been slashed in the meantime.
+    fn _pay(deal: &Deal<T::AccountId, T::Balance, T::BlockNumber>, until: T::BlockNumber) -> T::Balance {
+        if until <= deal.paid_until {
+            return T::Balance::sa(0);
+        }
+        let amount = Self::_cost(deal, until - deal.paid_until);
+        let repatriated = match <providers::Module<T>>::provider(deal.provider) {
+            Some(provider) => <balances::Module<T> as ReservableCurrency<_>>::repatriate_reserved(
+                &deal.publisher,
+                &provider.account,
+                amount,
+            ),
+            None => Err(ERR_PROVIDER_NOT_EXIST),
+        };
+        match repatriated {
+            Ok(remaining) => amount - remaining,
+            // a withdrawn provider or a reaped provider account can't be paid anymore
+            Err(_) => {
+                <balances::Module<T> as ReservableCurrency<_>>::unreserve(&deal.publisher, amount);
+                T::Balance::sa(0)
+            }
+        }
+    }
+
+    /// Pays the provider until the block, returns the rest to the publisher and removes the deal
+    fn _close(id: u64, deal: Deal<T::AccountId, T::Balance, T::BlockNumber>, until: T::BlockNumber) {
+        let remaining = match Self::_end_of(&deal) {
+            Some(end) => {
+                let until = if until < end { until } else { end };
+                let paid = Self::_pay(&deal, until);
+                if paid > T::Balance::sa(0) {
+                    Self::deposit_event(RawEvent::Paid(id, paid));
+                }
+                Self::_cost(&deal, end - until)
+            }
+            None => Self::_cost(&deal, deal.duration),
+        };
+        <balances::Module<T> as ReservableCurrency<_>>::unreserve(&deal.publisher, remaining);
+
+        <Deals<T>>::remove(id);
+        <DealsOfDid<T>>::mutate(&deal.did, |deals| deals.retain(|deal_id| *deal_id != id));
+    }
+}
+
+impl<T: Trait> metalog::OnDeleteRequest for Module<T> {
+    /// Ends the deals and offers of the deleted DID, the provider is paid until this block
+    fn on_delete_request(did: &Vec<u8>) {
+        let block_number = <system::Module<T>>::block_number();
+        for (id, deal) in Self::deals_of(did) {
+            Self::_close(id, deal, block_number);
+            Self::deposit_event(RawEvent::DealEndedByDeletion(id));
+        }
+    }
+}
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock::{create, new_test_ext, Origin, Test};
+    use runtime_io::with_externalities;
+    use runtime_primitives::traits::OnFinalize;
+    use support::{assert_noop, assert_ok, dispatch::Dispatchable};
+
+    type Balances = balances::Module<Test>;
+    type System = system::Module<Test>;
+    type Providers = providers::Module<Test>;
+    type Deals = Module<Test>;
+
+    const PUBLISHER: u64 = 1;
+    const PROVIDER: u64 = 2;
+    const STAKE: u64 = 10000;
+
+    /// Stores the DID and registers the provider with ID 0
+    fn setup(did: Vec<u8>) {
+        create(PUBLISHER, did);
+        let _ = Balances::make_free_balance_be(&PUBLISHER, 1000);
+        let _ = Balances::make_free_balance_be(&PROVIDER, STAKE);
+        assert_ok!(Providers::register(Origin::signed(PROVIDER), vec![1], 5, STAKE));
+    }
+
+    #[test]
+    fn create_deal_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            assert_noop!(
+                Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 0, 2, 100),
+                metalog::ERR_DID_NOT_EXIST
+            );
+            setup(did.clone());
+            assert_noop!(Deals::create_deal(Origin::signed(3), did.clone(), 0, 2, 100), metalog::ERR_NOT_OWNER);
+            assert_noop!(
+                Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 1, 2, 100),
+                ERR_PROVIDER_NOT_ACTIVE
+            );
+            assert_noop!(Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 0, 0, 100), ERR_PRICE_ZERO);
+            assert_noop!(Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 0, 2, 0), ERR_DEAL_DURATION);
+            assert_noop!(
+                Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 0, 2, MAX_DEAL_PERIOD + 1),
+                ERR_DEAL_DURATION
+            );
+            assert!(Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 0, 20, 100).is_err());
+
+            assert_ok!(Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 0, 2, 100));
+            assert_eq!(Balances::reserved_balance(&PUBLISHER), 200);
+            assert_eq!(Deals::deals_of_did(&did), vec![0]);
+            assert_eq!(Deals::deal(0).unwrap().start, None);
+
+            // an offer can be withdrawn in full
+            assert_noop!(Deals::cancel_deal(Origin::signed(3), 0), ERR_NOT_PARTY);
+            assert_ok!(Deals::cancel_deal(Origin::signed(PUBLISHER), 0));
+            assert_eq!(Balances::reserved_balance(&PUBLISHER), 0);
+            assert_eq!(Balances::free_balance(&PUBLISHER), 1000);
+            assert!(Deals::deals_of(&did).is_empty());
+        });
+    }
+
+    #[test]
+    fn accept_and_claim_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            setup(did.clone());
+            assert_ok!(Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 0, 2, 100));
+            assert_noop!(Deals::claim(Origin::signed(PROVIDER), 0), ERR_DEAL_NOT_STARTED);
+            assert_noop!(Deals::accept_deal(Origin::signed(PUBLISHER), 0), ERR_NOT_PROVIDER);
+
+            System::set_block_number(10);
+            assert_ok!(Deals::accept_deal(Origin::signed(PROVIDER), 0));
+            assert_noop!(Deals::accept_deal(Origin::signed(PROVIDER), 0), ERR_DEAL_STARTED);
+            assert_eq!(Deals::deals_ending_at(110), vec![0]);
+
+            System::set_block_number(30);
+            assert_ok!(Deals::claim(Origin::signed(PROVIDER), 0));
+            assert_eq!(Balances::free_balance(&PROVIDER), 40);
+            assert_eq!(Balances::reserved_balance(&PUBLISHER), 160);
+
+            // claims stop at the end of the deal
+            System::set_block_number(200);
+            assert_ok!(Deals::claim(Origin::signed(PROVIDER), 0));
+            assert_eq!(Balances::free_balance(&PROVIDER), 200);
+            assert_eq!(Balances::reserved_balance(&PUBLISHER), 0);
+        });
+    }
+
+    #[test]
+    fn slashed_reserve_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            setup(did.clone());
+            assert_ok!(Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 0, 2, 100));
+            assert_ok!(Deals::accept_deal(Origin::signed(PROVIDER), 0));
+            let _ = <Balances as ReservableCurrency<_>>::slash_reserved(&PUBLISHER, 150);
+
+            // only what is left of the reserve is paid
+            System::set_block_number(20);
+            assert_ok!(Deals::claim(Origin::signed(PROVIDER), 0));
+            assert_eq!(Balances::free_balance(&PROVIDER), 40);
+            System::set_block_number(70);
+            assert_ok!(Deals::claim(Origin::signed(PROVIDER), 0));
+            assert_eq!(Balances::free_balance(&PROVIDER), 50);
+            assert_eq!(Balances::reserved_balance(&PUBLISHER), 0);
+
+            assert_ok!(Deals::cancel_deal(Origin::signed(PUBLISHER), 0));
+            assert_eq!(Balances::free_balance(&PROVIDER), 50);
+            assert_eq!(Balances::free_balance(&PUBLISHER), 800);
+            assert_eq!(Deals::deal(0), None);
+        });
+    }
+
+    #[test]
+    fn deal_expiry_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            setup(did.clone());
+            assert_ok!(Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 0, 2, 100));
+            assert_ok!(Deals::accept_deal(Origin::signed(PROVIDER), 0));
+            System::set_block_number(40);
+            assert_ok!(Deals::claim(Origin::signed(PROVIDER), 0));
+
+            <Deals as OnFinalize<u64>>::on_finalize(100);
+            assert_eq!(Deals::deal(0), None);
+            assert_eq!(Deals::deals_of_did(&did), Vec::<u64>::new());
+            assert_eq!(Balances::free_balance(&PROVIDER), 200);
+            assert_eq!(Balances::free_balance(&PUBLISHER), 800);
+            assert_eq!(Balances::reserved_balance(&PUBLISHER), 0);
+        });
+    }
+
+    #[test]
+    fn offer_expiry_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            setup(did.clone());
+            assert_ok!(Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 0, 2, 100));
+            assert_eq!(Deals::deals_ending_at(OFFER_PERIOD), vec![0]);
+
+            <Deals as OnFinalize<u64>>::on_finalize(OFFER_PERIOD);
+            assert_eq!(Deals::deal(0), None);
+            assert!(Deals::deals_of(&did).is_empty());
+            assert_eq!(Balances::free_balance(&PUBLISHER), 1000);
+            assert_eq!(Balances::reserved_balance(&PUBLISHER), 0);
+
+            // accepted deals outlive the offer period
+            assert_ok!(Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 0, 2, 100));
+            assert_ok!(Deals::accept_deal(Origin::signed(PROVIDER), 1));
+            <Deals as OnFinalize<u64>>::on_finalize(OFFER_PERIOD);
+            assert!(Deals::deal(1).is_some());
+        });
+    }
+
+    #[test]
+    fn delete_request_ends_deals() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            setup(did.clone());
+            assert_ok!(Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 0, 2, 100));
+            assert_ok!(Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 0, 1, 50));
+            System::set_block_number(10);
+            assert_ok!(Deals::accept_deal(Origin::signed(PROVIDER), 0));
+
+            System::set_block_number(30);
+            let delete = metalog::Call::<Test>::change_license_code(did.clone(), 1);
+            assert_ok!(delete.dispatch(Origin::signed(PUBLISHER)));
+            assert_eq!(Deals::deal(0), None);
+            assert_eq!(Deals::deal(1), None);
+            assert!(Deals::deals_of(&did).is_empty());
+            assert_eq!(Balances::free_balance(&PROVIDER), 40);
+            assert_eq!(Balances::free_balance(&PUBLISHER), 960);
+            assert_eq!(Balances::reserved_balance(&PUBLISHER), 0);
+        });
+    }
+
+    #[test]
+    fn cancel_deal_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            setup(did.clone());
+            assert_ok!(Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 0, 2, 100));
+            assert_ok!(Deals::accept_deal(Origin::signed(PROVIDER), 0));
+
+            System::set_block_number(25);
+            assert_ok!(Deals::cancel_deal(Origin::signed(PROVIDER), 0));
+            assert_eq!(Deals::deal(0), None);
+            assert_eq!(Balances::free_balance(&PROVIDER), 50);
+            assert_eq!(Balances::free_balance(&PUBLISHER), 950);
+            assert_eq!(Balances::reserved_balance(&PUBLISHER), 0);
+
+            // the expiry of a canceled deal is skipped
+            <Deals as OnFinalize<u64>>::on_finalize(100);
+            assert_eq!(Balances::free_balance(&PROVIDER), 50);
+        });
+    }
+
+    #[test]
+    fn confirm_unavailable_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            setup(did.clone());
+            let _ = Balances::make_free_balance_be(&5, 100);
+            assert_ok!(Deals::create_deal(Origin::signed(PUBLISHER), did.clone(), 0, 2, 100));
+            assert_noop!(Deals::confirm_unavailable(Origin::ROOT, 0), ERR_DEAL_NOT_STARTED);
+
+            System::set_block_number(10);
+            assert_ok!(Deals::accept_deal(Origin::signed(PROVIDER), 0));
+            assert_noop!(Deals::confirm_unavailable(Origin::ROOT, 0), ERR_REPORT_NOT_EXIST);
+
+            System::set_block_number(30);
+            let report = availability::Call::<Test>::report_unavailable(did.clone());
+            assert_ok!(report.dispatch(Origin::signed(5)));
+            System::set_block_number(50);
+            assert_noop!(
+                Deals::confirm_unavailable(Origin::signed(5), 0),
+                federation::ERR_RANK_INSUFFICIENT
+            );
+            assert_ok!(Deals::confirm_unavailable(Origin::ROOT, 0));
+
+            // paid until the report, not until the confirmation
+            assert_eq!(Deals::deal(0), None);
+            assert_eq!(Balances::free_balance(&PROVIDER), 40);
+            assert_eq!(Balances::free_balance(&PUBLISHER), 960);
+            assert_eq!(Balances::reserved_balance(&PUBLISHER), 0);
+        });
+    }
+}
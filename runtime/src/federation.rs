@@ -20,6 +20,8 @@ use system::ensure_signed;
 const ERR_RANK_LOWER: &str = "Candidate already has the maximum rank";
 const ERR_RANK_LOCK: &str = "Ranks can only be changed 4 weeks after the last change";
 const ERR_RANK_HIGHER: &str = "Rank needs to be above guest rank to be canceled";
+pub const ERR_RANK_INSUFFICIENT: &str = "Your federation rank is too low for this action";
+const ERR_BAD_ORIGIN: &str = "Origin needs to be root or signed";
 
 const ERR_VOTE_MIN_STAKE: &str = "To vote you need to stake at least the minimum amount of tokens";
 const ERR_VOTE_MIN_LOCK: &str = "To vote you need to lock at least for one week";
@@ -33,12 +35,12 @@ const ERR_OVERFLOW_VOTES: &str = "Overflow adding new votes";
 const ERR_OVERFLOW_COUNT: &str = "Overflow increasing vote count";
 const ERR_UNDERFLOW: &str = "Underflow subtraction error";
 
-const ADMIRAL_RANK: u16 = 5;
-const SECTION31_RANK: u16 = 4;
-const CAPTAIN_RANK: u16 = 3;
-const ENGINEER_RANK: u16 = 2;
-const CREW_RANK: u16 = 1;
-const GUEST_RANK: u16 = 0;
+pub const ADMIRAL_RANK: u16 = 5;
+pub const SECTION31_RANK: u16 = 4;
+pub const CAPTAIN_RANK: u16 = 3;
+pub const ENGINEER_RANK: u16 = 2;
+pub const CREW_RANK: u16 = 1;
+pub const GUEST_RANK: u16 = 0;
 
 /// The module's configuration trait.
 pub trait Trait: system::Trait + balances::Trait {
@@ -279,6 +281,18 @@ decl_event!(
 );
 
 impl<T: Trait> Module<T> {
+	/// Ensures the origin is root or a member with at least the given rank
+	pub fn ensure_root_or_rank(origin: T::Origin, min_rank: u16) -> Result {
+		match origin.into() {
+			Some(system::RawOrigin::Root) => Ok(()),
+			Some(system::RawOrigin::Signed(who)) => {
+				ensure!(Self::candidate_by_account(&who).current_rank >= min_rank, ERR_RANK_INSUFFICIENT);
+				Ok(())
+			},
+			_ => Err(ERR_BAD_ORIGIN),
+		}
+	}
+
 	fn _check_vote(intended_rank: u16, vote_index: u64, lock_time: T::BlockNumber) -> Result{
 		ensure!(intended_rank > GUEST_RANK, ERR_VOTE_RANK);
 		ensure!(lock_time >= T::BlockNumber::sa(Self::min_lock()), ERR_VOTE_MIN_LOCK);
@@ -450,6 +464,25 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn ensure_root_or_rank_works() {
+		with_externalities(&mut new_test_ext(), || {
+			assert_ok!(FederationModule::ensure_root_or_rank(Origin::ROOT, ADMIRAL_RANK));
+			assert_noop!(FederationModule::ensure_root_or_rank(Origin::signed(0), CREW_RANK), ERR_RANK_INSUFFICIENT);
+			let candidate = Candidate {
+				current_rank: CAPTAIN_RANK,
+				intended_rank: CAPTAIN_RANK,
+				votes_for: 3001,
+				votes_against: 0,
+				last_change: 0,
+				challenge_start: 0,
+			};
+			let _ = FederationModule::updated_rank_store(0, candidate);
+			assert_ok!(FederationModule::ensure_root_or_rank(Origin::signed(0), CAPTAIN_RANK));
+			assert_noop!(FederationModule::ensure_root_or_rank(Origin::signed(0), ADMIRAL_RANK), ERR_RANK_INSUFFICIENT);
+		});
+	}
+
 	#[test]
 	fn cancel_membership_works() {
 		with_externalities(&mut new_test_ext(), || {
@@ -0,0 +1,306 @@
+//! # Follow List Module
+//!
+//! Consumers decide themselves which publishers they trust. The Follow List module lets every account
+//! publish named lists of publisher accounts, which interfaces can apply to filter metalogs deterministically.
+//! The order of a list is kept, earlier publishers rank higher.
+//! Every list reserves a deposit per list and per publisher, which is returned once the list is deleted.
+
+use parity_codec::{Decode, Encode};
+use rstd::vec::Vec;
+use runtime_primitives::traits::As;
+use support::{
+    decl_event, decl_module, decl_storage,
+    dispatch::Result,
+    ensure,
+    traits::ReservableCurrency,
+    StorageMap,
+};
+use system::ensure_signed;
+
+use crate::metalog::{self, Metalog};
+
+const ERR_LIST_NOT_EXIST: &str = "This follow list does not exist";
+const ERR_LIST_EMPTY: &str = "A follow list needs at least one publisher";
+const ERR_LIST_DUPLICATE: &str = "A publisher can only be listed once";
+const ERR_LIST_SIZE_LIMIT: &str = "This follow list has too many publishers";
+const ERR_LISTS_LIMIT: &str = "You have reached the maximum number of follow lists";
+
+const ERR_BYTEARRAY_LIMIT_LIST_NAME: &str = "List name bytearray is too large";
+
+const BYTEARRAY_LIMIT_LIST_NAME: usize = 50;
+/// Maximum number of publishers per list
+const MAX_LIST_SIZE: usize = 100;
+/// Maximum number of lists per account
+const MAX_LISTS: usize = 20;
+
+/// Deposit reserved for every list
+const LIST_DEPOSIT: u64 = 1000;
+/// Deposit reserved for every publisher on a list
+const PUBLISHER_DEPOSIT: u64 = 100;
+
+/// The module's configuration trait.
+pub trait Trait: metalog::Trait {
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+/// Named list of followed publishers
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct FollowList<AccountId, Balance> {
+    /// Publishers in rank order
+    pub publishers: Vec<AccountId>,
+    /// Reserved deposit
+    pub deposit: Balance,
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as FollowList {
+        /// Follow lists by (list owner, list name)
+        Lists get(list_of): map (T::AccountId, Vec<u8>) => Option<FollowList<T::AccountId, T::Balance>>;
+
+        /// Names of the lists of an account
+        ListNames get(list_names_of): map T::AccountId => Vec<Vec<u8>>;
+    }
+}
+
+decl_module! {
+    /// The module declaration.
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        fn deposit_event<T>() = default;
+
+        /// Create or replace a follow list, the deposit is adjusted to the new size
+        fn set_list(origin, name: Vec<u8>, publishers: Vec<T::AccountId>) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(name.len() <= BYTEARRAY_LIMIT_LIST_NAME, ERR_BYTEARRAY_LIMIT_LIST_NAME);
+            ensure!(!publishers.is_empty(), ERR_LIST_EMPTY);
+            ensure!(publishers.len() <= MAX_LIST_SIZE, ERR_LIST_SIZE_LIMIT);
+            for (i, publisher) in publishers.iter().enumerate() {
+                ensure!(!publishers[..i].contains(publisher), ERR_LIST_DUPLICATE);
+            }
+
+            let key = (sender.clone(), name.clone());
+            let is_new = !<Lists<T>>::exists(&key);
+            let old_deposit = match Self::list_of(&key) {
+                Some(list) => list.deposit,
+                None => {
+                    ensure!(Self::list_names_of(&sender).len() < MAX_LISTS, ERR_LISTS_LIMIT);
+                    T::Balance::sa(0)
+                }
+            };
+            let deposit = Self::deposit_for(publishers.len());
+            if deposit > old_deposit {
+                <balances::Module<T> as ReservableCurrency<_>>::reserve(&sender, deposit - old_deposit)?;
+            } else {
+                <balances::Module<T> as ReservableCurrency<_>>::unreserve(&sender, old_deposit - deposit);
+            }
+
+            if is_new {
+                <ListNames<T>>::mutate(&sender, |names| names.push(name.clone()));
+            }
+            let size = publishers.len() as u64;
+            <Lists<T>>::insert(&key, FollowList { publishers, deposit });
+
+            Self::deposit_event(RawEvent::ListUpdated(sender, name, size, deposit));
+            Ok(())
+        }
+
+        /// Delete a follow list and return its deposit
+        fn delete_list(origin, name: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let list = <Lists<T>>::take((sender.clone(), name.clone())).ok_or(ERR_LIST_NOT_EXIST)?;
+            <ListNames<T>>::mutate(&sender, |names| names.retain(|n| n != &name));
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&sender, list.deposit);
+
+            Self::deposit_event(RawEvent::ListDeleted(sender, name));
+            Ok(())
+        }
+    }
+}
+
+decl_event!(
+    pub enum Event<T> where
+        <T as system::Trait>::AccountId,
+        <T as balances::Trait>::Balance
+    {
+        /// list owner, list name, number of publishers, reserved deposit
+        ListUpdated(AccountId, Vec<u8>, u64, Balance),
+        /// list owner, list name
+        ListDeleted(AccountId, Vec<u8>),
+    }
+);
+
+impl<T: Trait> Module<T> {
+    /// Deposit of a list with the given number of publishers
+    pub fn deposit_for(size: usize) -> T::Balance {
+        T::Balance::sa(LIST_DEPOSIT + PUBLISHER_DEPOSIT * size as u64)
+    }
+
+    /// Publishers of a list in rank order, empty if the list doesn't exist
+    pub fn publishers_of(list_owner: &T::AccountId, name: &Vec<u8>) -> Vec<T::AccountId> {
+        Self::list_of((list_owner.clone(), name.clone()))
+            .map(|list| list.publishers)
+            .unwrap_or_default()
+    }
+
+    /// Metalogs of the DID published by an account on the list
+    pub fn metalogs_by_did(
+        list_owner: &T::AccountId,
+        name: &Vec<u8>,
+        did: &Vec<u8>,
+    ) -> Vec<Metalog<T::AccountId, T::Moment, T::Balance>> {
+        Self::_filter(list_owner, name, <metalog::Module<T>>::metalog_by_did(did))
+    }
+
+    /// Metalogs the unique name points to, published by an account on the list
+    pub fn metalogs_by_name(
+        list_owner: &T::AccountId,
+        name: &Vec<u8>,
+        unique_name: &Vec<u8>,
+    ) -> Vec<Metalog<T::AccountId, T::Moment, T::Balance>> {
+        Self::_filter(list_owner, name, <metalog::Module<T>>::metalog_by_name(unique_name))
+    }
+
+    /// Winning record of a name, the publishers of the list are trusted in their list order
+    pub fn resolve_name(
+        list_owner: &T::AccountId,
+        name: &Vec<u8>,
+        unique_name: &Vec<u8>,
+    ) -> Option<(T::AccountId, Metalog<T::AccountId, T::Moment, T::Balance>)> {
+        <metalog::Module<T>>::resolve_name(&Self::publishers_of(list_owner, name), unique_name)
+    }
+
+    /// Keeps the metalog, if its publisher is on the list and no delete request has been filed
+    /// The publisher is the creator, later owners of a sold DID don't change who published it
+    fn _filter(
+        list_owner: &T::AccountId,
+        name: &Vec<u8>,
+        metalog: Option<Metalog<T::AccountId, T::Moment, T::Balance>>,
+    ) -> Vec<Metalog<T::AccountId, T::Moment, T::Balance>> {
+        let publishers = Self::publishers_of(list_owner, name);
+        metalog
+            .into_iter()
+            .filter(|record| publishers.contains(&record.creator))
+            .filter(|record| <metalog::Module<T>>::deletion_of_did(&record.did).is_none())
+            .collect()
+    }
+}
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock::{create, new_test_ext, Origin, Test};
+    use runtime_io::with_externalities;
+    use support::{assert_noop, assert_ok, dispatch::Dispatchable};
+
+    type Balances = balances::Module<Test>;
+    type Metalog = metalog::Module<Test>;
+    type FollowList = Module<Test>;
+
+    #[test]
+    fn set_list_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let name = vec![1];
+            let _ = Balances::make_free_balance_be(&1, 10000);
+            assert_noop!(FollowList::set_list(Origin::signed(1), name.clone(), vec![]), ERR_LIST_EMPTY);
+            assert_noop!(
+                FollowList::set_list(Origin::signed(1), name.clone(), vec![2, 3, 2]),
+                ERR_LIST_DUPLICATE
+            );
+            assert_noop!(
+                FollowList::set_list(Origin::signed(1), name.clone(), (0..MAX_LIST_SIZE as u64 + 1).collect()),
+                ERR_LIST_SIZE_LIMIT
+            );
+
+            assert_ok!(FollowList::set_list(Origin::signed(1), name.clone(), vec![3, 2]));
+            assert_eq!(FollowList::publishers_of(&1, &name), vec![3, 2]);
+            assert_eq!(FollowList::list_names_of(&1), vec![name.clone()]);
+            assert_eq!(Balances::reserved_balance(&1), LIST_DEPOSIT + 2 * PUBLISHER_DEPOSIT);
+
+            // shrinking the list returns part of the deposit
+            assert_ok!(FollowList::set_list(Origin::signed(1), name.clone(), vec![2]));
+            assert_eq!(FollowList::publishers_of(&1, &name), vec![2]);
+            assert_eq!(FollowList::list_names_of(&1), vec![name.clone()]);
+            assert_eq!(Balances::reserved_balance(&1), LIST_DEPOSIT + PUBLISHER_DEPOSIT);
+
+            for i in 1..MAX_LISTS as u8 {
+                assert_ok!(FollowList::set_list(Origin::signed(1), vec![1, i], vec![2]));
+            }
+            assert_noop!(FollowList::set_list(Origin::signed(1), vec![2], vec![2]), ERR_LISTS_LIMIT);
+
+            // deposits need to be covered
+            assert!(FollowList::set_list(Origin::signed(2), name.clone(), vec![1]).is_err());
+        });
+    }
+
+    #[test]
+    fn delete_list_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let name = vec![1];
+            let _ = Balances::make_free_balance_be(&1, 10000);
+            assert_noop!(FollowList::delete_list(Origin::signed(1), name.clone()), ERR_LIST_NOT_EXIST);
+            assert_ok!(FollowList::set_list(Origin::signed(1), name.clone(), vec![2, 3]));
+            assert_ok!(FollowList::set_list(Origin::signed(1), vec![2], vec![2]));
+
+            assert_ok!(FollowList::delete_list(Origin::signed(1), name.clone()));
+            assert_eq!(FollowList::list_of((1, name.clone())), None);
+            assert_eq!(FollowList::list_names_of(&1), vec![vec![2]]);
+            assert_eq!(Balances::reserved_balance(&1), LIST_DEPOSIT + PUBLISHER_DEPOSIT);
+            assert_eq!(Balances::free_balance(&1), 10000 - LIST_DEPOSIT - PUBLISHER_DEPOSIT);
+        });
+    }
+
+    #[test]
+    fn filtered_metalogs_work() {
+        with_externalities(&mut new_test_ext(), || {
+            let name = vec![1];
+            let _ = Balances::make_free_balance_be(&1, 10000);
+            let _ = Balances::make_free_balance_be(&3, 10000);
+            create(2, vec![10]);
+            create(3, vec![11]);
+            let un = vec![1, 2, 3, 4, 5, 6];
+            assert_ok!(Metalog::buy_unique_name(Origin::signed(3), vec![11], un.clone()));
+
+            assert!(FollowList::metalogs_by_did(&1, &name, &vec![10]).is_empty());
+            assert_ok!(FollowList::set_list(Origin::signed(1), name.clone(), vec![2]));
+            assert_eq!(FollowList::metalogs_by_did(&1, &name, &vec![10])[0].did, vec![10]);
+            assert!(FollowList::metalogs_by_did(&1, &name, &vec![11]).is_empty());
+            assert!(FollowList::metalogs_by_name(&1, &name, &un).is_empty());
+
+            assert_ok!(FollowList::set_list(Origin::signed(1), name.clone(), vec![2, 3]));
+            assert_eq!(FollowList::metalogs_by_name(&1, &name, &un)[0].did, vec![11]);
+            assert!(FollowList::metalogs_by_did(&1, &name, &vec![12]).is_empty());
+
+            let _ = Balances::make_free_balance_be(&2, 10000);
+            assert_ok!(Metalog::set_namespaced_name(Origin::signed(2), un.clone(), vec![10]));
+            assert_eq!(FollowList::resolve_name(&1, &name, &un).map(|(publisher, _)| publisher), Some(2));
+            assert_ok!(FollowList::set_list(Origin::signed(1), name.clone(), vec![3, 2]));
+            assert_eq!(FollowList::resolve_name(&1, &name, &un).map(|(_, metalog)| metalog.did), Some(vec![11]));
+        });
+    }
+
+    #[test]
+    fn filter_by_publisher_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let name = vec![1];
+            let _ = Balances::make_free_balance_be(&1, 10000);
+            create(2, vec![10]);
+            assert_ok!(FollowList::set_list(Origin::signed(1), name.clone(), vec![2]));
+
+            // the DID stays listed after its publisher transferred it
+            let transfer = metalog::Call::<Test>::transfer_ownership(4, vec![10]);
+            assert_ok!(transfer.dispatch(Origin::signed(2)));
+            assert_eq!(FollowList::metalogs_by_did(&1, &name, &vec![10])[0].did, vec![10]);
+            assert_ok!(FollowList::set_list(Origin::signed(1), name.clone(), vec![4]));
+            assert!(FollowList::metalogs_by_did(&1, &name, &vec![10]).is_empty());
+
+            // DIDs with a delete request are dropped
+            assert_ok!(FollowList::set_list(Origin::signed(1), name.clone(), vec![2]));
+            assert_ok!(Metalog::change_license_code(Origin::signed(4), vec![10], 1));
+            assert!(FollowList::metalogs_by_did(&1, &name, &vec![10]).is_empty());
+        });
+    }
+}
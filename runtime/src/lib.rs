@@ -6,7 +6,7 @@
 
 use client::{
     block_builder::api::{self as block_builder_api, CheckInherentsResult, InherentData},
-    impl_runtime_apis, runtime_api,
+    decl_runtime_apis, impl_runtime_apis, runtime_api,
 };
 use parity_codec::{Decode, Encode};
 #[cfg(feature = "std")]
@@ -56,10 +56,128 @@ pub type BlockNumber = u64;
 /// Index of an account's extrinsic in the chain.
 pub type Nonce = u64;
 
+/// The type for recording an account's balance.
+pub type Balance = u128;
+
+/// A timestamp: seconds since the unix epoch.
+pub type Moment = u64;
+
 // Specific Starlog modules
-mod metalog;
+pub mod metalog;
+
+pub mod federation;
+
+pub mod availability;
+
+pub mod providers;
+
+pub mod deals;
+
+pub mod marketplace;
+
+pub mod licensing;
+
+mod follow_list;
+
+mod name_auction;
+
+pub mod treasury;
+
+#[cfg(test)]
+mod mock;
+
+/// Metalog as returned to clients by the runtime API.
+pub type MetalogRecord = metalog::Metalog<AccountId, Moment, Balance>;
+/// Attestation of a pinning provider as returned to clients by the runtime API.
+pub type AttestationRecord = availability::Attestation<BlockNumber>;
+/// Pinning provider as returned to clients by the runtime API.
+pub type ProviderRecord = providers::Provider<AccountId, Balance, BlockNumber>;
+/// Pinning deal as returned to clients by the runtime API.
+pub type DealRecord = deals::Deal<AccountId, Balance, BlockNumber>;
+/// Listing of a DID for sale as returned to clients by the runtime API.
+pub type ListingRecord = marketplace::Listing<AccountId, Balance>;
+/// License receipt as returned to clients by the runtime API.
+pub type ReceiptRecord = licensing::Receipt<AccountId, BlockNumber>;
+
+decl_runtime_apis! {
+    /// Read access to metalogs, so clients don't need to decode raw storage.
+    pub trait MetalogApi {
+        /// Metalog of a DID.
+        fn metalog_by_did(did: Vec<u8>) -> Option<MetalogRecord>;
+        /// Metalog a unique name points to.
+        fn metalog_by_name(unique_name: Vec<u8>) -> Option<MetalogRecord>;
+        /// Owner of a DID.
+        fn owner_of(did: Vec<u8>) -> Option<AccountId>;
+        /// Page of the metalogs owned by an account, starting at the index `start`.
+        fn metalogs_of_owner(account: AccountId, start: u64, limit: u64) -> Vec<MetalogRecord>;
+        /// Fee of a unique name, if it can be bought directly.
+        fn name_price(unique_name: Vec<u8>) -> Option<Balance>;
+        /// Page of all claimed unique names, starting at the index `start`.
+        fn unique_names(start: u64, limit: u64) -> Vec<Vec<u8>>;
+        /// Publisher and metalog winning a name, the publishers are trusted in the given rank order.
+        fn resolve_name(publishers: Vec<AccountId>, name: Vec<u8>) -> Option<(AccountId, MetalogRecord)>;
+    }
+
+    /// Metalogs filtered by the follow lists of consumers.
+    pub trait FollowListApi {
+        /// Publishers of a follow list in rank order.
+        fn follow_list(list_owner: AccountId, name: Vec<u8>) -> Vec<AccountId>;
+        /// Metalogs of a DID, published by an account on the follow list.
+        fn followed_metalogs_by_did(list_owner: AccountId, name: Vec<u8>, did: Vec<u8>) -> Vec<MetalogRecord>;
+        /// Metalogs of a unique name, published by an account on the follow list.
+        fn followed_metalogs_by_name(list_owner: AccountId, name: Vec<u8>, unique_name: Vec<u8>) -> Vec<MetalogRecord>;
+        /// Publisher and metalog winning a name, the publishers are trusted in the order of the follow list.
+        fn resolve_name_by_list(list_owner: AccountId, name: Vec<u8>, unique_name: Vec<u8>) -> Option<(AccountId, MetalogRecord)>;
+    }
+
+    /// Availability of the content of DIDs.
+    pub trait AvailabilityApi {
+        /// Active attestations and recent unavailability reports of a DID.
+        fn availability_of(did: Vec<u8>) -> availability::AvailabilityScore;
+        /// Attestations of a DID by provider, including expired ones.
+        fn attestations_of(did: Vec<u8>) -> Vec<(AccountId, AttestationRecord)>;
+        /// Whether the account has registered an active staked pinning provider.
+        fn is_provider(account: AccountId) -> bool;
+    }
+
+    /// Registry of staked pinning providers and their deals with publishers.
+    pub trait ProvidersApi {
+        /// Pinning provider by ID.
+        fn provider(id: u64) -> Option<ProviderRecord>;
+        /// ID of the provider registered by an account.
+        fn provider_of(account: AccountId) -> Option<u64>;
+        /// Whether metalogs can reference the provider.
+        fn is_active_provider(id: u64) -> bool;
+        /// Open deals to pin the content of a DID by deal ID.
+        fn deals_of(did: Vec<u8>) -> Vec<(u64, DealRecord)>;
+    }
+
+    /// DIDs listed for sale.
+    pub trait MarketplaceApi {
+        /// Page of the active listings by DID, starting at the index `start`.
+        fn listings(start: u64, limit: u64) -> Vec<(Vec<u8>, ListingRecord)>;
+        /// Listing of a DID, if the seller still owns it.
+        fn listing_of(did: Vec<u8>) -> Option<ListingRecord>;
+    }
+
+    /// Licenses to use the content of DIDs.
+    pub trait LicensingApi {
+        /// Whether the account has bought a license for the DID.
+        fn has_license(account: AccountId, did: Vec<u8>) -> bool;
+        /// License receipts of the account for the DID.
+        fn licenses_of(account: AccountId, did: Vec<u8>) -> Vec<ReceiptRecord>;
+        /// Price of a license for the DID, if it is for sale.
+        fn license_price(did: Vec<u8>, license_code: u16) -> Option<Balance>;
+    }
 
-mod federation;
+    /// Funds of the treasury.
+    pub trait TreasuryApi {
+        /// Funds available for spending.
+        fn pot() -> Balance;
+        /// Account holding the pot.
+        fn pot_account() -> AccountId;
+    }
+}
 
 /// Opaque types. These are used by the CLI to instantiate machinery that don't need to know
 /// the specifics of the runtime. They can then be made to be agnostic over specific formats
@@ -102,7 +220,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
     spec_name: create_runtime_str!("starlog"),
     impl_name: create_runtime_str!("starlog"),
     authoring_version: 3,
-    spec_version: 3,
+    spec_version: 4,
     impl_version: 0,
     apis: RUNTIME_API_VERSIONS,
 };
@@ -169,13 +287,13 @@ impl indices::Trait for Runtime {
 
 impl timestamp::Trait for Runtime {
     /// A timestamp: seconds since the unix epoch.
-    type Moment = u64;
+    type Moment = Moment;
     type OnTimestampSet = Aura;
 }
 
 impl balances::Trait for Runtime {
     /// The type for recording an account's balance.
-    type Balance = u128;
+    type Balance = Balance;
     /// What to do if an account's free balance gets zeroed.
     type OnFreeBalanceZero = (Contract);
     /// What to do if a new account is created.
@@ -208,12 +326,46 @@ impl contract::Trait for Runtime {
 // Specific Starlog modules
 impl metalog::Trait for Runtime {
     type Event = Event;
+    type PublisherSignature = metalog::PublisherSignature;
+    type OnDeleteRequest = Deals;
 }
 
 impl federation::Trait for Runtime {
     type Event = Event;
 }
 
+impl follow_list::Trait for Runtime {
+    type Event = Event;
+}
+
+impl availability::Trait for Runtime {
+    type Event = Event;
+}
+
+impl providers::Trait for Runtime {
+    type Event = Event;
+}
+
+impl deals::Trait for Runtime {
+    type Event = Event;
+}
+
+impl marketplace::Trait for Runtime {
+    type Event = Event;
+}
+
+impl licensing::Trait for Runtime {
+    type Event = Event;
+}
+
+impl name_auction::Trait for Runtime {
+    type Event = Event;
+}
+
+impl treasury::Trait for Runtime {
+    type Event = Event;
+}
+
 construct_runtime!(
 	pub enum Runtime with Log(InternalLog: DigestItem<Hash, AuthorityId, AuthoritySignature>) where
 		Block = Block,
@@ -230,13 +382,21 @@ construct_runtime!(
 
         Contract: contract::{Module, Call, Storage, Config<T>, Event<T>},
 		// Specific Starlog modules
-		Metalog: metalog::{Module, Call, Storage, Event<T>},
+		Metalog: metalog::{Module, Call, Storage, Event<T>, Config<T>},
 		Federation: federation::{Module, Call, Storage, Event<T>, Config<T>},
+		NameAuction: name_auction::{Module, Call, Storage, Event<T>},
+		FollowList: follow_list::{Module, Call, Storage, Event<T>},
+		Availability: availability::{Module, Call, Storage, Event<T>},
+		Providers: providers::{Module, Call, Storage, Event<T>},
+		Deals: deals::{Module, Call, Storage, Event<T>},
+		Marketplace: marketplace::{Module, Call, Storage, Event<T>},
+		Licensing: licensing::{Module, Call, Storage, Event<T>},
+		Treasury: treasury::{Module, Call, Event<T>},
 	}
 );
 
 /// The address format for describing accounts.
-type Address = <Indices as StaticLookup>::Source;
+pub type Address = <Indices as StaticLookup>::Source;
 /// Block header type as expected by this runtime.
 pub type Header = generic::Header<BlockNumber, BlakeTwo256, Log>;
 /// Block type as expected by this runtime.
@@ -248,6 +408,8 @@ pub type UncheckedExtrinsic =
     generic::UncheckedMortalCompactExtrinsic<Address, Nonce, Call, AccountSignature>;
 /// Extrinsic type that has already been checked.
 pub type CheckedExtrinsic = generic::CheckedExtrinsic<AccountId, Nonce, Call>;
+/// Event with the phase it was deposited in, as stored by the system module.
+pub type EventRecord = system::EventRecord<Event>;
 /// Executive: handles dispatch to the various modules.
 pub type Executive =
     executive::Executive<Runtime, Block, system::ChainContext<Runtime>, Balances, AllModules>;
@@ -318,6 +480,120 @@ impl_runtime_apis! {
         }
     }
 
+    impl self::MetalogApi<Block> for Runtime {
+        fn metalog_by_did(did: Vec<u8>) -> Option<MetalogRecord> {
+            Metalog::metalog_by_did(&did)
+        }
+
+        fn metalog_by_name(unique_name: Vec<u8>) -> Option<MetalogRecord> {
+            Metalog::metalog_by_name(&unique_name)
+        }
+
+        fn owner_of(did: Vec<u8>) -> Option<AccountId> {
+            Metalog::owner_of_did(&did)
+        }
+
+        fn metalogs_of_owner(account: AccountId, start: u64, limit: u64) -> Vec<MetalogRecord> {
+            Metalog::metalogs_of_owner(&account, start, limit)
+        }
+
+        fn name_price(unique_name: Vec<u8>) -> Option<Balance> {
+            Metalog::name_price(&unique_name)
+        }
+
+        fn unique_names(start: u64, limit: u64) -> Vec<Vec<u8>> {
+            Metalog::unique_names(start, limit)
+        }
+
+        fn resolve_name(publishers: Vec<AccountId>, name: Vec<u8>) -> Option<(AccountId, MetalogRecord)> {
+            Metalog::resolve_name(&publishers, &name)
+        }
+    }
+
+    impl self::FollowListApi<Block> for Runtime {
+        fn follow_list(list_owner: AccountId, name: Vec<u8>) -> Vec<AccountId> {
+            FollowList::publishers_of(&list_owner, &name)
+        }
+
+        fn followed_metalogs_by_did(list_owner: AccountId, name: Vec<u8>, did: Vec<u8>) -> Vec<MetalogRecord> {
+            FollowList::metalogs_by_did(&list_owner, &name, &did)
+        }
+
+        fn followed_metalogs_by_name(list_owner: AccountId, name: Vec<u8>, unique_name: Vec<u8>) -> Vec<MetalogRecord> {
+            FollowList::metalogs_by_name(&list_owner, &name, &unique_name)
+        }
+
+        fn resolve_name_by_list(list_owner: AccountId, name: Vec<u8>, unique_name: Vec<u8>) -> Option<(AccountId, MetalogRecord)> {
+            FollowList::resolve_name(&list_owner, &name, &unique_name)
+        }
+    }
+
+    impl self::AvailabilityApi<Block> for Runtime {
+        fn availability_of(did: Vec<u8>) -> availability::AvailabilityScore {
+            Availability::availability_of(&did)
+        }
+
+        fn attestations_of(did: Vec<u8>) -> Vec<(AccountId, AttestationRecord)> {
+            Availability::attestations_of(&did)
+        }
+
+        fn is_provider(account: AccountId) -> bool {
+            Availability::is_provider(&account)
+        }
+    }
+
+    impl self::ProvidersApi<Block> for Runtime {
+        fn provider(id: u64) -> Option<ProviderRecord> {
+            Providers::provider(id)
+        }
+
+        fn provider_of(account: AccountId) -> Option<u64> {
+            Providers::provider_of(&account)
+        }
+
+        fn is_active_provider(id: u64) -> bool {
+            Providers::is_active(id)
+        }
+
+        fn deals_of(did: Vec<u8>) -> Vec<(u64, DealRecord)> {
+            Deals::deals_of(&did)
+        }
+    }
+
+    impl self::MarketplaceApi<Block> for Runtime {
+        fn listings(start: u64, limit: u64) -> Vec<(Vec<u8>, ListingRecord)> {
+            Marketplace::listings(start, limit)
+        }
+
+        fn listing_of(did: Vec<u8>) -> Option<ListingRecord> {
+            Marketplace::active_listing(&did)
+        }
+    }
+
+    impl self::LicensingApi<Block> for Runtime {
+        fn has_license(account: AccountId, did: Vec<u8>) -> bool {
+            Licensing::has_license(&account, &did)
+        }
+
+        fn licenses_of(account: AccountId, did: Vec<u8>) -> Vec<ReceiptRecord> {
+            Licensing::receipts_of((account, did))
+        }
+
+        fn license_price(did: Vec<u8>, license_code: u16) -> Option<Balance> {
+            Licensing::license_price((did, license_code))
+        }
+    }
+
+    impl self::TreasuryApi<Block> for Runtime {
+        fn pot() -> Balance {
+            Treasury::pot()
+        }
+
+        fn pot_account() -> AccountId {
+            Treasury::account_id()
+        }
+    }
+
     // FIXME: needs to be commended out for tests
     impl consensus_authorities::AuthoritiesApi<Block> for Runtime {
         fn authorities() -> Vec<AuthorityId> {
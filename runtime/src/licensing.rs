@@ -0,0 +1,256 @@
+//! # Licensing Module
+//!
+//! The Licensing module sells the right to use the content of a DID, the ownership stays with the owner.
+//! Owners set a price per DID and registered license code, buyers pay the price to the current owner.
+//! Prices end once the DID is transferred, the new owner sets their own prices.
+//! Every purchase is recorded as a license receipt, which frontends can check before serving the content.
+
+use parity_codec::{Decode, Encode};
+use rstd::vec::Vec;
+use runtime_primitives::traits::As;
+use support::{decl_event, decl_module, decl_storage, dispatch::Result, ensure, StorageMap};
+use system::ensure_signed;
+
+use crate::metalog;
+
+const ERR_DID_NOT_EXIST: &str = "This DID does not exist";
+const ERR_LICENSE_NOT_REGISTERED: &str = "Only registered license codes can be sold";
+const ERR_LICENSE_NOT_FOR_SALE: &str = "This license isn't for sale for this DID";
+const ERR_LICENSE_OWNED: &str = "You already bought this license";
+const ERR_BUY_OWN: &str = "You own this DID";
+const ERR_PRICE_ZERO: &str = "The price needs to be above zero";
+const ERR_PRICE_ABOVE_MAX: &str = "The price is above your maximum price";
+
+/// The module's configuration trait.
+pub trait Trait: metalog::Trait {
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+/// Proof of a license purchase
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Receipt<AccountId, BlockNumber> {
+    pub buyer: AccountId,
+    pub did: Vec<u8>,
+    pub license_code: u16,
+    /// Block of the purchase
+    pub block: BlockNumber,
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Licensing {
+        /// License prices by (DID, license code) together with the owner, who set them
+        Prices get(offer_of): map (Vec<u8>, u16) => Option<(T::AccountId, T::Balance)>;
+
+        /// License receipts by (buyer, DID)
+        Receipts get(receipts_of): map (T::AccountId, Vec<u8>) => Vec<Receipt<T::AccountId, T::BlockNumber>>;
+    }
+}
+
+decl_module! {
+    /// The module declaration.
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        fn deposit_event<T>() = default;
+
+        /// Offer a license for an owned DID, replaces an earlier price
+        pub fn set_license_price(origin, did: Vec<u8>, license_code: u16, price: T::Balance) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            <metalog::Module<T>>::check_ownership(&sender, &did)?;
+            ensure!(<metalog::Module<T>>::license_by_code(license_code).is_some(), ERR_LICENSE_NOT_REGISTERED);
+            ensure!(price > T::Balance::sa(0), ERR_PRICE_ZERO);
+
+            <Prices<T>>::insert((did.clone(), license_code), (sender, price));
+
+            Self::deposit_event(RawEvent::LicensePriceSet(did, license_code, price));
+            Ok(())
+        }
+
+        /// Stop selling a license, bought licenses stay valid
+        pub fn remove_license_price(origin, did: Vec<u8>, license_code: u16) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            <metalog::Module<T>>::check_ownership(&sender, &did)?;
+            ensure!(<Prices<T>>::exists((did.clone(), license_code)), ERR_LICENSE_NOT_FOR_SALE);
+
+            <Prices<T>>::remove((did.clone(), license_code));
+
+            Self::deposit_event(RawEvent::LicensePriceRemoved(did, license_code));
+            Ok(())
+        }
+
+        /// Buy a license for a DID, the price goes to the current owner minus the creator's royalty.
+        /// Fails if the owner has raised the price above the maximum price of the buyer.
+        pub fn buy_license(origin, did: Vec<u8>, license_code: u16, max_price: T::Balance) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let owner = <metalog::Module<T>>::owner_of_did(&did).ok_or(ERR_DID_NOT_EXIST)?;
+            <metalog::Module<T>>::check_ownership(&owner, &did)?;
+            ensure!(owner != sender, ERR_BUY_OWN);
+            let price = Self::license_price((did.clone(), license_code)).ok_or(ERR_LICENSE_NOT_FOR_SALE)?;
+            ensure!(price <= max_price, ERR_PRICE_ABOVE_MAX);
+            let mut receipts = Self::receipts_of((sender.clone(), did.clone()));
+            ensure!(!receipts.iter().any(|receipt| receipt.license_code == license_code), ERR_LICENSE_OWNED);
+
+            <metalog::Module<T>>::pay_sale(&sender, &owner, &did, price)?;
+
+            receipts.push(Receipt {
+                buyer: sender.clone(),
+                did: did.clone(),
+                license_code,
+                block: <system::Module<T>>::block_number(),
+            });
+            <Receipts<T>>::insert((sender.clone(), did.clone()), receipts);
+
+            Self::deposit_event(RawEvent::LicenseBought(sender, did, license_code, price));
+            Ok(())
+        }
+    }
+}
+
+decl_event!(
+    pub enum Event<T> where
+        <T as system::Trait>::AccountId,
+        <T as balances::Trait>::Balance
+    {
+        /// did, license code, price
+        LicensePriceSet(Vec<u8>, u16, Balance),
+        /// did, license code
+        LicensePriceRemoved(Vec<u8>, u16),
+        /// buyer, did, license code, price
+        LicenseBought(AccountId, Vec<u8>, u16, Balance),
+    }
+);
+
+impl<T: Trait> Module<T> {
+    /// Price of a license, none if the DID has been transferred since the price was set
+    pub fn license_price(key: (Vec<u8>, u16)) -> Option<T::Balance> {
+        let (setter, price) = Self::offer_of(&key)?;
+        if <metalog::Module<T>>::owner_of_did(&key.0) == Some(setter) {
+            Some(price)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the account has bought any license for the DID
+    pub fn has_license(account: &T::AccountId, did: &Vec<u8>) -> bool {
+        <Receipts<T>>::exists((account.clone(), did.clone()))
+    }
+}
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock::{create, new_test_ext, Origin, Test};
+    use runtime_io::with_externalities;
+    use support::{assert_noop, assert_ok, dispatch::Dispatchable};
+
+    type Balances = balances::Module<Test>;
+    type System = system::Module<Test>;
+    type Licensing = Module<Test>;
+
+    /// Registered license code
+    const LICENSE: u16 = 4;
+
+    fn register_license(license_code: u16) {
+        let call = metalog::Call::<Test>::register_license(license_code, b"CC-BY-4.0".to_vec(), vec![1], vec![2], true);
+        assert_ok!(call.dispatch(Origin::ROOT));
+    }
+
+    #[test]
+    fn set_license_price_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            create(1, did.clone());
+            assert_noop!(
+                Licensing::set_license_price(Origin::signed(1), did.clone(), LICENSE, 10),
+                ERR_LICENSE_NOT_REGISTERED
+            );
+            register_license(LICENSE);
+            assert_noop!(
+                Licensing::set_license_price(Origin::signed(2), did.clone(), LICENSE, 10),
+                metalog::ERR_NOT_OWNER
+            );
+            assert_noop!(Licensing::set_license_price(Origin::signed(1), did.clone(), LICENSE, 0), ERR_PRICE_ZERO);
+
+            assert_ok!(Licensing::set_license_price(Origin::signed(1), did.clone(), LICENSE, 10));
+            assert_eq!(Licensing::license_price((did.clone(), LICENSE)), Some(10));
+
+            assert_ok!(Licensing::remove_license_price(Origin::signed(1), did.clone(), LICENSE));
+            assert_eq!(Licensing::license_price((did.clone(), LICENSE)), None);
+            assert_noop!(
+                Licensing::remove_license_price(Origin::signed(1), did.clone(), LICENSE),
+                ERR_LICENSE_NOT_FOR_SALE
+            );
+        });
+    }
+
+    #[test]
+    fn buy_license_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            assert_noop!(Licensing::buy_license(Origin::signed(2), did.clone(), LICENSE, 10), ERR_DID_NOT_EXIST);
+            create(1, did.clone());
+            register_license(LICENSE);
+            assert_noop!(
+                Licensing::buy_license(Origin::signed(2), did.clone(), LICENSE, 10),
+                ERR_LICENSE_NOT_FOR_SALE
+            );
+            assert_ok!(Licensing::set_license_price(Origin::signed(1), did.clone(), LICENSE, 10));
+            assert_noop!(Licensing::buy_license(Origin::signed(1), did.clone(), LICENSE, 10), ERR_BUY_OWN);
+            assert_noop!(Licensing::buy_license(Origin::signed(2), did.clone(), LICENSE, 9), ERR_PRICE_ABOVE_MAX);
+            assert!(Licensing::buy_license(Origin::signed(2), did.clone(), LICENSE, 10).is_err());
+            assert!(!Licensing::has_license(&2, &did));
+
+            let _ = Balances::make_free_balance_be(&2, 100);
+            System::set_block_number(5);
+            assert_ok!(Licensing::buy_license(Origin::signed(2), did.clone(), LICENSE, 10));
+            assert_noop!(
+                Licensing::buy_license(Origin::signed(2), did.clone(), LICENSE, 10),
+                ERR_LICENSE_OWNED
+            );
+            assert!(Licensing::has_license(&2, &did));
+            assert_eq!(Licensing::receipts_of((2, did.clone())), vec![Receipt {
+                buyer: 2,
+                did: did.clone(),
+                license_code: LICENSE,
+                block: 5,
+            }]);
+            assert_eq!(Balances::free_balance(&1), 10);
+            assert_eq!(Balances::free_balance(&2), 90);
+
+            // the license stays valid after the DID has been transferred
+            let transfer = metalog::Call::<Test>::transfer_ownership(3, did.clone());
+            assert_ok!(transfer.dispatch(Origin::signed(1)));
+            assert!(Licensing::has_license(&2, &did));
+        });
+    }
+
+    #[test]
+    fn transfer_ends_prices_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            create(1, did.clone());
+            register_license(LICENSE);
+            assert_ok!(Licensing::set_license_price(Origin::signed(1), did.clone(), LICENSE, 10));
+            let transfer = metalog::Call::<Test>::transfer_ownership(3, did.clone());
+            assert_ok!(transfer.dispatch(Origin::signed(1)));
+
+            // the price of the previous owner isn't offered by the new owner
+            let _ = Balances::make_free_balance_be(&2, 100);
+            assert_eq!(Licensing::license_price((did.clone(), LICENSE)), None);
+            assert_noop!(
+                Licensing::buy_license(Origin::signed(2), did.clone(), LICENSE, 10),
+                ERR_LICENSE_NOT_FOR_SALE
+            );
+
+            assert_ok!(Licensing::set_license_price(Origin::signed(3), did.clone(), LICENSE, 20));
+            assert_ok!(Licensing::buy_license(Origin::signed(2), did.clone(), LICENSE, 20));
+            assert_eq!(Balances::free_balance(&3), 20);
+            assert_eq!(Balances::free_balance(&2), 80);
+        });
+    }
+}
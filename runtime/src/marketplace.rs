@@ -0,0 +1,271 @@
+//! # Marketplace Module
+//!
+//! The Marketplace module sells DIDs at a fixed price.
+//! Owners list their DIDs for sale, a buyer pays the price to the seller and receives the ownership in the same transaction.
+//! Listings of DIDs, which have changed hands in another way or have been deleted, are stale and can't be bought.
+
+use parity_codec::{Decode, Encode};
+use rstd::vec::Vec;
+use runtime_primitives::traits::As;
+use support::{decl_event, decl_module, decl_storage, dispatch::Result, ensure, StorageMap, StorageValue};
+use system::ensure_signed;
+
+use crate::metalog;
+
+const ERR_LISTING_NOT_EXIST: &str = "This DID is not listed for sale";
+const ERR_LISTING_STALE: &str = "The seller no longer owns this DID";
+const ERR_NOT_SELLER: &str = "You are neither the seller nor the owner of this DID";
+const ERR_BUY_OWN: &str = "You already own this DID";
+const ERR_PRICE_ABOVE_MAX: &str = "The price is above your maximum price";
+const ERR_PRICE_ZERO: &str = "The price needs to be above zero";
+
+const ERR_OVERFLOW: &str = "Overflow adding a new listing";
+
+/// Maximum number of listings returned by one page
+const MAX_PAGE_SIZE: u64 = 100;
+
+/// The module's configuration trait.
+pub trait Trait: metalog::Trait {
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+/// Offer to sell a DID
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Listing<AccountId, Balance> {
+    /// Owner at the time of listing
+    pub seller: AccountId,
+    pub price: Balance,
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Marketplace {
+        /// Listings by DID
+        Listings get(listing_of): map Vec<u8> => Option<Listing<T::AccountId, T::Balance>>;
+
+        /// Array of all listed DIDs for enumeration
+        ListingArray get(listed_did_by_index): map u64 => Vec<u8>;
+        ListingCount get(listing_count): u64;
+        ListingIndex: map Vec<u8> => u64;
+    }
+}
+
+decl_module! {
+    /// The module declaration.
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        fn deposit_event<T>() = default;
+
+        /// List an owned DID for sale, replaces an earlier listing
+        pub fn list_for_sale(origin, did: Vec<u8>, price: T::Balance) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            <metalog::Module<T>>::check_ownership(&sender, &did)?;
+            ensure!(price > T::Balance::sa(0), ERR_PRICE_ZERO);
+            if !<Listings<T>>::exists(&did) {
+                ensure!(Self::listing_count().checked_add(1).is_some(), ERR_OVERFLOW);
+                Self::_list(&did);
+            }
+
+            <Listings<T>>::insert(&did, Listing { seller: sender.clone(), price });
+
+            Self::deposit_event(RawEvent::Listed(sender, did, price));
+            Ok(())
+        }
+
+        /// Cancel a listing, also the current owner can remove a stale listing
+        pub fn cancel_listing(origin, did: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let listing = Self::listing_of(&did).ok_or(ERR_LISTING_NOT_EXIST)?;
+            let owner = <metalog::Module<T>>::owner_of_did(&did);
+            ensure!(listing.seller == sender || owner == Some(sender), ERR_NOT_SELLER);
+
+            Self::_remove_listing(&did);
+
+            Self::deposit_event(RawEvent::ListingCanceled(did));
+            Ok(())
+        }
+
+        /// Buy a listed DID, the price goes to the seller minus the creator's royalty and the ownership to the buyer.
+        /// Fails if the seller has raised the price above the maximum price of the buyer.
+        pub fn buy(origin, did: Vec<u8>, max_price: T::Balance) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let listing = Self::listing_of(&did).ok_or(ERR_LISTING_NOT_EXIST)?;
+            ensure!(listing.seller != sender, ERR_BUY_OWN);
+            ensure!(listing.price <= max_price, ERR_PRICE_ABOVE_MAX);
+            <metalog::Module<T>>::check_ownership(&listing.seller, &did).map_err(|_| ERR_LISTING_STALE)?;
+            <metalog::Module<T>>::check_transfer(&listing.seller, &sender, &did)?;
+
+            <metalog::Module<T>>::pay_sale(&sender, &listing.seller, &did, listing.price)?;
+            // can't fail after the transfer has been checked
+            <metalog::Module<T>>::transfer_sold(listing.seller.clone(), sender.clone(), &did)?;
+            Self::_remove_listing(&did);
+
+            Self::deposit_event(RawEvent::Sold(listing.seller, sender, did, listing.price));
+            Ok(())
+        }
+    }
+}
+
+decl_event!(
+    pub enum Event<T> where
+        <T as system::Trait>::AccountId,
+        <T as balances::Trait>::Balance
+    {
+        /// seller, did, price
+        Listed(AccountId, Vec<u8>, Balance),
+        ListingCanceled(Vec<u8>),
+        /// seller, buyer, did, price
+        Sold(AccountId, AccountId, Vec<u8>, Balance),
+    }
+);
+
+impl<T: Trait> Module<T> {
+    /// Page of the listings, at most MAX_PAGE_SIZE listings from the start index
+    /// Stale listings are skipped, so a page can be shorter than the limit.
+    pub fn listings(start: u64, limit: u64) -> Vec<(Vec<u8>, Listing<T::AccountId, T::Balance>)> {
+        let end = start
+            .saturating_add(limit.min(MAX_PAGE_SIZE))
+            .min(Self::listing_count());
+        (start..end)
+            .map(|index| Self::listed_did_by_index(index))
+            .filter_map(|did| Self::active_listing(&did).map(|listing| (did, listing)))
+            .collect()
+    }
+
+    /// Listing of a DID, if the seller still owns it
+    pub fn active_listing(did: &Vec<u8>) -> Option<Listing<T::AccountId, T::Balance>> {
+        Self::listing_of(did)
+            .filter(|listing| <metalog::Module<T>>::check_ownership(&listing.seller, did).is_ok())
+    }
+
+    fn _remove_listing(did: &Vec<u8>) {
+        <Listings<T>>::remove(did);
+        Self::_unlist(did);
+    }
+
+    /// Adds the DID to the array of all listed DIDs
+    fn _list(did: &Vec<u8>) {
+        let count = Self::listing_count();
+        <ListingArray<T>>::insert(count, did);
+        <ListingIndex<T>>::insert(did, count);
+        <ListingCount<T>>::put(count + 1);
+    }
+
+    /// Removes the DID from the array of all listed DIDs, the last DID takes its place
+    fn _unlist(did: &Vec<u8>) {
+        let count = Self::listing_count();
+        let index = <ListingIndex<T>>::get(did);
+        if index >= count || Self::listed_did_by_index(index) != *did {
+            return;
+        }
+        let last_index = count - 1;
+        if index != last_index {
+            let last_did = <ListingArray<T>>::get(last_index);
+            <ListingArray<T>>::insert(index, &last_did);
+            <ListingIndex<T>>::insert(&last_did, index);
+        }
+        <ListingArray<T>>::remove(last_index);
+        <ListingIndex<T>>::remove(did);
+        <ListingCount<T>>::put(last_index);
+    }
+}
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock::{create, new_test_ext, Origin, Test};
+    use runtime_io::with_externalities;
+    use support::{assert_noop, assert_ok, dispatch::Dispatchable};
+
+    type Balances = balances::Module<Test>;
+    type Metalog = metalog::Module<Test>;
+    type Marketplace = Module<Test>;
+
+    #[test]
+    fn list_for_sale_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            assert_noop!(Marketplace::list_for_sale(Origin::signed(1), did.clone(), 10), metalog::ERR_DID_NOT_EXIST);
+            create(1, did.clone());
+            create(1, vec![2]);
+            assert_noop!(Marketplace::list_for_sale(Origin::signed(2), did.clone(), 10), metalog::ERR_NOT_OWNER);
+            assert_noop!(Marketplace::list_for_sale(Origin::signed(1), did.clone(), 0), ERR_PRICE_ZERO);
+
+            assert_ok!(Marketplace::list_for_sale(Origin::signed(1), did.clone(), 10));
+            assert_ok!(Marketplace::list_for_sale(Origin::signed(1), vec![2], 20));
+            // relisting changes the price
+            assert_ok!(Marketplace::list_for_sale(Origin::signed(1), did.clone(), 15));
+            assert_eq!(Marketplace::listing_count(), 2);
+            assert_eq!(Marketplace::listings(0, 10), vec![
+                (did.clone(), Listing { seller: 1, price: 15 }),
+                (vec![2], Listing { seller: 1, price: 20 }),
+            ]);
+
+            assert_noop!(Marketplace::cancel_listing(Origin::signed(2), did.clone()), ERR_NOT_SELLER);
+            assert_ok!(Marketplace::cancel_listing(Origin::signed(1), did.clone()));
+            assert_noop!(Marketplace::cancel_listing(Origin::signed(1), did.clone()), ERR_LISTING_NOT_EXIST);
+            assert_eq!(Marketplace::listing_count(), 1);
+            assert_eq!(Marketplace::listed_did_by_index(0), vec![2]);
+        });
+    }
+
+    #[test]
+    fn buy_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            // the creator receives 10% of later sales
+            let call = metalog::Call::<Test>::create_metalog(did.clone(), 0, vec![1], 0, vec![2], None, 100, None);
+            assert_ok!(call.dispatch(Origin::signed(1)));
+            assert_noop!(Marketplace::buy(Origin::signed(2), did.clone(), 10), ERR_LISTING_NOT_EXIST);
+            assert_ok!(Marketplace::list_for_sale(Origin::signed(1), did.clone(), 10));
+            assert_noop!(Marketplace::buy(Origin::signed(1), did.clone(), 10), ERR_BUY_OWN);
+            assert!(Marketplace::buy(Origin::signed(2), did.clone(), 10).is_err());
+            assert_eq!(Metalog::owner_of_did(&did), Some(1));
+
+            let _ = Balances::make_free_balance_be(&2, 100);
+            assert_ok!(Marketplace::buy(Origin::signed(2), did.clone(), 10));
+            assert_eq!(Metalog::owner_of_did(&did), Some(2));
+            assert_eq!(Metalog::owner_meta_count(&1), 0);
+            assert_eq!(Metalog::owner_meta_count(&2), 1);
+            assert_eq!(Balances::free_balance(&1), 10);
+            assert_eq!(Balances::free_balance(&2), 90);
+            assert_eq!(Marketplace::listing_of(&did), None);
+            assert_eq!(Marketplace::listing_count(), 0);
+
+            let _ = Balances::make_free_balance_be(&3, 100);
+            assert_ok!(Marketplace::list_for_sale(Origin::signed(2), did.clone(), 20));
+            // the seller raised the price after the buyer sent the transaction
+            assert_ok!(Marketplace::list_for_sale(Origin::signed(2), did.clone(), 50));
+            assert_noop!(Marketplace::buy(Origin::signed(3), did.clone(), 20), ERR_PRICE_ABOVE_MAX);
+            assert_ok!(Marketplace::buy(Origin::signed(3), did.clone(), 50));
+            assert_eq!(Metalog::owner_of_did(&did), Some(3));
+            assert_eq!(Balances::free_balance(&1), 15);
+            assert_eq!(Balances::free_balance(&2), 135);
+            assert_eq!(Balances::free_balance(&3), 50);
+        });
+    }
+
+    #[test]
+    fn stale_listing_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![1];
+            create(1, did.clone());
+            assert_ok!(Marketplace::list_for_sale(Origin::signed(1), did.clone(), 10));
+            let transfer = metalog::Call::<Test>::transfer_ownership(3, did.clone());
+            assert_ok!(transfer.dispatch(Origin::signed(1)));
+
+            let _ = Balances::make_free_balance_be(&2, 100);
+            assert_noop!(Marketplace::buy(Origin::signed(2), did.clone(), 10), ERR_LISTING_STALE);
+            assert!(Marketplace::listings(0, 10).is_empty());
+            assert_eq!(Marketplace::active_listing(&did), None);
+
+            // the new owner removes the stale listing
+            assert_ok!(Marketplace::cancel_listing(Origin::signed(3), did.clone()));
+            assert_eq!(Marketplace::listing_count(), 0);
+        });
+    }
+}
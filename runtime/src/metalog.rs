@@ -1,84 +1,229 @@
 //! # Metalog Module
 //!
-//!	The Metalog module is the key module of Starlog. It handles the DID, unique name, license code, storage location and timestamp.
+//!	The Metalog module is the key module of Starlog. It handles the DID, unique name, license code, file hash, price, metadata hash, pinning provider, creator royalty and timestamp.
 //! For more information see https://github.com/PACTCare/Stars-Network/blob/master/WHITEPAPER.md#--starlog--substrate-
 
 use parity_codec::{Decode, Encode};
-use rstd::vec::Vec;
-use runtime_primitives::traits::As;
+use primitives::{ed25519, sr25519};
+use rstd::{borrow::Borrow, vec::Vec};
+use runtime_primitives::traits::{As, Lazy, Verify};
 use support::{
     decl_event, decl_module, decl_storage,
     dispatch::Result,
     ensure,
-    traits::{Currency, ExistenceRequirement, WithdrawReason},
-    StorageMap,
+    traits::{Currency, ExistenceRequirement, ReservableCurrency, WithdrawReason},
+    Parameter, StorageMap, StorageValue,
 };
 use system::ensure_signed;
 
+use crate::{federation, providers, treasury};
+
 // FIXME: needs to be removed for building the runtime
 // use runtime_io::{with_storage, StorageOverlay, ChildrenStorageOverlay};
 
 const ERR_DID_ALREADY_CLAIMED: &str = "This DID has already been claimed.";
-const ERR_DID_NOT_EXIST: &str = "This DID does not exist";
+pub const ERR_DID_NOT_EXIST: &str = "This DID does not exist";
 const ERR_DID_NO_OWNER: &str = "No one owens this did";
+const ERR_DID_DELETED: &str = "A delete request has been filed for this DID";
 
 const ERR_UN_ALREADY_CLAIMED: &str = "This unique name has already been claimed.";
+const ERR_UN_NOT_OWNER: &str = "You are not the owner of this unique name";
+const ERR_UN_NOT_SET: &str = "This DID has no unique name";
+const ERR_UN_ALREADY_SET: &str = "This DID already has a unique name, rename it instead";
+const ERR_UN_AUCTION: &str = "Short unique names can only be bought in a name auction";
+const ERR_UN_NOT_IN_NAMESPACE: &str = "This name is not set in your namespace";
 
 const ERR_LICENSE_INVALID: &str = "Invalid license code";
+const ERR_LICENSE_NOT_REGISTERED: &str = "This license code is not registered";
+const ERR_LICENSE_ALREADY_REGISTERED: &str = "This license code has already been registered";
+
+const ERR_SIGNATURE_INVALID: &str = "Invalid publisher signature";
 
 const ERR_OVERFLOW: &str = "Overflow adding new metadata";
 const ERR_UNDERFLOW: &str = "Underflow removing metadata";
 
-const ERR_NOT_OWNER: &str = "You are not the owner";
+pub const ERR_NOT_OWNER: &str = "You are not the owner";
+const ERR_PROVIDER_NOT_ACTIVE: &str = "This pinning provider is not active";
+const ERR_ROYALTY_INVALID: &str = "The royalty is above the maximum royalty";
+const ERR_TRANSFER_SELF: &str = "You already own this DID";
+const ERR_SELLER_SHARE_LOW: &str = "The share of the seller is below the existential deposit of the seller's new account";
+
+const ERR_INDEX_CORRUPTED: &str = "The owned metalog index is inconsistent";
+const ERR_INDEX_REPAIRED: &str = "The owned metalog index of this account has already been repaired";
 
 const ERR_BYTEARRAY_LIMIT_DID: &str = "DID bytearray is too large";
 const ERR_BYTEARRAY_LIMIT_NAME: &str = "Name bytearray is too large";
+const ERR_BYTEARRAY_LIMIT_HASH: &str = "Hash bytearray is too large";
+const ERR_BYTEARRAY_LIMIT_LICENSE: &str = "License bytearray is too large";
 
-const BYTEARRAY_LIMIT_DID: usize = 100;
-const BYTEARRAY_LIMIT_NAME: usize = 50;
+const ERR_PARAMETER_INVALID: &str = "Invalid parameter value";
 
+const BYTEARRAY_LIMIT_DID: usize = 100;
+const BYTEARRAY_LIMIT_HASH: usize = 100;
+const BYTEARRAY_LIMIT_LICENSE: usize = 50;
+/// Upper bound for the governance-adjustable name limit
+const MAX_NAME_LIMIT: u64 = 100;
+/// Maximum number of metalogs returned per page to clients
+const MAX_PAGE_SIZE: u64 = 100;
+
+const NO_LICENSE: u16 = 0;
 const DELETE_LICENSE: u16 = 1;
 
-//TODO: Needs to be updatable via votes!
-const FEE_PER_USED_CHAR: u64 = 100;
-
-/// The module's configuration traits are timestamp and balance
-pub trait Trait: timestamp::Trait + balances::Trait {
+/// Minimum federation rank to register new license codes
+const LICENSE_REGISTRATION_RANK: u16 = federation::CAPTAIN_RANK;
+/// Minimum federation rank to change the name pricing parameters
+const PARAMETER_RANK: u16 = federation::ADMIRAL_RANK;
+
+/// Unique names are leased for one year with 6 seconds blocktime
+const NAME_LEASE_PERIOD: u64 = 5256000;
+/// After the lease expired, the owner can still renew for four weeks
+const NAME_GRACE_PERIOD: u64 = 403200;
+/// Percentage of the last paid fee refunded, if a unique name is released before its lease ends
+const NAME_REFUND_PERCENT: u64 = 50;
+/// At most half of a sale price goes to the creator, in per mill
+const MAX_ROYALTY: u16 = 500;
+/// Deposit reserved for every name in the namespace of a publisher
+const NAMESPACED_NAME_DEPOSIT: u64 = 100;
+
+/// The module's configuration traits are timestamp, balance, federation, treasury and providers
+pub trait Trait: timestamp::Trait + balances::Trait + federation::Trait + treasury::Trait + providers::Trait {
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+    /// Detached signature of a publisher over the metalog payload
+    type PublisherSignature: Parameter + Verify<Signer = Self::AccountId>;
+    /// Handler of delete requests
+    type OnDeleteRequest: OnDeleteRequest;
+}
+
+/// Handler of delete requests, e.g. to end the pinning deals of the DID
+pub trait OnDeleteRequest {
+    /// Called after the DID has been tombstoned
+    fn on_delete_request(did: &Vec<u8>);
+}
+
+impl OnDeleteRequest for () {
+    fn on_delete_request(_did: &Vec<u8>) {}
+}
+
+/// Detached sr25519 or ed25519 publisher signature
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum PublisherSignature {
+    Sr25519(sr25519::Signature),
+    Ed25519(ed25519::Signature),
+}
+
+impl Verify for PublisherSignature {
+    type Signer = sr25519::Public;
+    fn verify<L: Lazy<[u8]>>(&self, msg: L, signer: &Self::Signer) -> bool {
+        match self {
+            PublisherSignature::Sr25519(signature) => signature.verify(msg, signer),
+            // same 32 bytes, interpreted as ed25519 public key
+            PublisherSignature::Ed25519(signature) => {
+                signature.verify(msg, &ed25519::Public(signer.0))
+            }
+        }
+    }
 }
 
 /// Key metalog struct
 #[derive(Encode, Decode, Default, Clone, PartialEq)]
 #[cfg_attr(feature = "std", derive(Debug))]
-pub struct Metalog<Time> {
+pub struct Metalog<AccountId, Time, Balance> {
     /// DID
     pub did: Vec<u8>,         // = primary key, can't be changed
     /// Unique Name
     pub unique_name: Vec<u8>, // Default = 0
     /// License code
     pub license_code: u16,    // 0 = no license code, 1 = delete request
+    /// Unique file hash
+    pub file_hash: Vec<u8>,
+    /// Price set by the publisher
+    pub price: Balance,
+    /// Metadata hash, off-chain information about the uploaded file
+    pub meta_hash: Vec<u8>,
+    /// ID of the pinning provider in the provider registry
+    pub provider: Option<u64>,
+    /// Original owner, receives the royalty of later sales
+    pub creator: AccountId,
+    /// Share of the creator in every later sale in per mill
+    pub royalty: u16,
     /// Timestamp
     pub time: Time,
 }
 
+/// Metalog layout of spec version 3 and earlier, still stored for DIDs, which haven't changed since
+#[derive(Encode, Decode)]
+struct LegacyMetalog<Time> {
+    did: Vec<u8>,
+    unique_name: Vec<u8>,
+    license_code: u16,
+    time: Time,
+}
+
+/// Registered license code
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct License {
+    /// SPDX identifier
+    pub spdx: Vec<u8>,
+    /// Human-readable name
+    pub name: Vec<u8>,
+    /// Hash of the terms document
+    pub terms_hash: Vec<u8>,
+    /// Commercial use allowed
+    pub commercial_use: bool,
+}
+
 decl_storage! {
     trait Store for Module<T: Trait> as Metalog {
-        /// Array of personal owned metalog data 
-        OwnedMetaArray get(metadata_of_owner_by_index): map (T::AccountId, u64) => Metalog<T::Moment>;
+        /// Array of DIDs per owner, the metalog itself is only stored under DidMeta
+        /// Earlier versions stored the full metalog, its encoding starts with the DID
+        OwnedMetaArray get(did_of_owner_by_index): map (T::AccountId, u64) => Vec<u8>;
 
         /// Number of stored metalogs per account
         OwnedMetaCount get(owner_meta_count): map T::AccountId => u64;
 
-        /// Index of DID
+        /// Position of a DID in the array of its owner, starting at 0
         OwnedMetaIndex: map Vec<u8> => u64;
+        /// Accounts whose array has been rebuilt after the index migration
+        IndexRepaired get(index_repaired): map T::AccountId => bool;
 
-        /// Query for unique names
-        UnMeta get(meta_of_un): map Vec<u8> => Metalog<T::Moment>;
+        /// DID a unique name points to, read from earlier full metalogs like OwnedMetaArray
+        UnMeta get(did_of_un): map Vec<u8> => Vec<u8>;
         UnOwner get(owner_of_un): map Vec<u8> => Option<T::AccountId>;
-
-        /// Query by DIDs
-        DidMeta get(meta_of_did): map Vec<u8> => Metalog<T::Moment>;
+        /// Block number at which the lease of a unique name ends
+        UnExpiry get(expiry_of_un): map Vec<u8> => T::BlockNumber;
+        /// Fee paid for the current lease of a unique name
+        UnFee get(fee_of_un): map Vec<u8> => T::Balance;
+        /// Unique names to be released at the end of a block, lease end + grace period
+        UnRelease get(uns_released_at): map T::BlockNumber => Vec<Vec<u8>>;
+        /// Array of all claimed unique names, so clients can enumerate them
+        UnArray get(un_by_index): map u64 => Vec<u8>;
+        UnCount get(un_count): u64;
+        UnIndex: map Vec<u8> => u64;
+
+        /// DIDs named by publishers in their own namespace, by (publisher, name)
+        /// Unlike unique names, the same name can be used by every publisher
+        NsName get(did_of_ns_name): map (T::AccountId, Vec<u8>) => Option<Vec<u8>>;
+
+        /// Query by DIDs, the canonical metalog, read with meta_of_did
+        /// Records of earlier versions are stored in the legacy layout until their next change
+        DidMeta: map Vec<u8> => Metalog<T::AccountId, T::Moment, T::Balance>;
         DidOwner get(owner_of_did): map Vec<u8> => Option<T::AccountId>;
+
+        /// License catalogue, 0 = no license code and 1 = delete request are built in
+        Licenses get(license_by_code): map u16 => Option<License>;
+
+        /// Block number of delete requests
+        DeletedAt get(deletion_of_did): map Vec<u8> => Option<T::BlockNumber>;
+
+        // parameters
+        /// Fee per unused character of a unique name
+        FeePerUsedChar get(fee_per_used_char) config(): u64 = 100;
+        /// Maximum length of a unique name
+        NameLimit get(name_limit) config(): u64 = 50;
+        /// Unique names shorter than this are sold through the name auction
+        ShortNameLength get(short_name_length) config(): u64 = 5;
     }
 
     //FIXME: needs to be removed for building the runtime
@@ -95,6 +240,12 @@ decl_storage! {
     // 					did: did.clone(),
     // 					unique_name: default_name,
     // 					license_code,
+    // 					file_hash: Vec::new(),
+    // 					price: T::Balance::sa(0),
+    // 					meta_hash: Vec::new(),
+    // 					provider: None,
+    // 					creator: acct.clone(),
+    // 					royalty: 0,
     // 					time,
     // 				};
     //                 let _ = <Module<T>>::_owner_store(acct.clone(), new_metadata);
@@ -109,17 +260,48 @@ decl_module! {
     pub struct Module<T: Trait> for enum Call where origin: T::Origin {
         fn deposit_event<T>() = default;
 
+        /// Releases unique names, which haven't been renewed within the grace period
+        fn on_finalize(n: T::BlockNumber) {
+            for unique_name in <UnRelease<T>>::take(n) {
+                if <UnOwner<T>>::exists(&unique_name) && Self::_release_block(&unique_name) == n {
+                    Self::_expire_name(&unique_name);
+                }
+            }
+        }
+
         /// Store initial metalog
+        /// If a publisher signature is provided, the publisher becomes the owner,
+        /// otherwise the sender
         fn create_metalog(
             origin,
             did: Vec<u8>,
-            license_code: u16) -> Result {
+            license_code: u16,
+            file_hash: Vec<u8>,
+            price: T::Balance,
+            meta_hash: Vec<u8>,
+            provider: Option<u64>,
+            royalty: u16,
+            publisher: Option<(T::AccountId, T::PublisherSignature)>) -> Result {
 
             let sender = ensure_signed(origin)?;
 
             ensure!(did.len() <= BYTEARRAY_LIMIT_DID, ERR_BYTEARRAY_LIMIT_DID);
+            ensure!(file_hash.len() <= BYTEARRAY_LIMIT_HASH, ERR_BYTEARRAY_LIMIT_HASH);
+            ensure!(meta_hash.len() <= BYTEARRAY_LIMIT_HASH, ERR_BYTEARRAY_LIMIT_HASH);
             ensure!(!<DidOwner<T>>::exists(&did), ERR_DID_ALREADY_CLAIMED);
             ensure!(license_code != DELETE_LICENSE, ERR_LICENSE_INVALID);
+            ensure!(royalty <= MAX_ROYALTY, ERR_ROYALTY_INVALID);
+            Self::_check_license(license_code)?;
+            Self::_check_provider(provider)?;
+
+            let owner = match publisher {
+                Some((publisher, signature)) => {
+                    let payload = Self::metalog_payload(&did, license_code, &file_hash, &price, &meta_hash, &provider, royalty);
+                    ensure!(signature.verify(&payload[..], &publisher), ERR_SIGNATURE_INVALID);
+                    publisher
+                }
+                None => sender,
+            };
 
             let time = <timestamp::Module<T>>::now();
 
@@ -129,15 +311,29 @@ decl_module! {
                 did,
                 unique_name: default_name,
                 license_code,
+                file_hash,
+                price,
+                meta_hash,
+                provider,
+                creator: owner.clone(),
+                royalty,
                 time,
             };
 
-            Self::_owner_store(sender.clone(), new_metadata.clone())?;
-            Self::deposit_event(RawEvent::Stored(sender, new_metadata.time, new_metadata.did));
+            Self::_owner_store(owner.clone(), new_metadata.clone())?;
+            Self::deposit_event(RawEvent::Stored(
+                owner,
+                new_metadata.time,
+                new_metadata.did,
+                new_metadata.file_hash,
+                new_metadata.price,
+                new_metadata.meta_hash,
+                new_metadata.provider,
+            ));
             Ok(())
         }
 
-        /// Transfer the ownership, Payment will be implemented in smart contracts
+        /// Transfer the ownership without payment, sales go through the marketplace
         fn transfer_ownership(origin, receiver: T::AccountId, did: Vec<u8>) -> Result {
             let sender = ensure_signed(origin)?;
             Self::_check_did_ownership(sender.clone(), &did)?;
@@ -147,75 +343,433 @@ decl_module! {
             Ok(())
         }
 
+        /// Change the pinning provider of an owned DID, none if the content isn't pinned by a provider
+        pub fn change_provider(origin, did: Vec<u8>, provider: Option<u64>) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            Self::_check_did_ownership(sender.clone(), &did)?;
+            Self::_check_provider(provider)?;
+
+            let mut metalog = Self::meta_of_did(&did);
+            metalog.provider = provider;
+            <DidMeta<T>>::insert(&did, &metalog);
+
+            Self::deposit_event(RawEvent::ProviderChanged(sender, did, provider));
+            Ok(())
+        }
+
         /// Buy a unique name
         pub fn buy_unique_name(origin, did: Vec<u8>, unique_name: Vec<u8>)-> Result{
             let sender = ensure_signed(origin)?;
 
             Self::_check_did_ownership(sender.clone(), &did)?;
 
-            ensure!(unique_name.len() <= BYTEARRAY_LIMIT_NAME, ERR_BYTEARRAY_LIMIT_NAME);
+            ensure!(unique_name.len() as u64 <= Self::name_limit(), ERR_BYTEARRAY_LIMIT_NAME);
+            ensure!(!Self::is_short_name(&unique_name), ERR_UN_AUCTION);
+
+            ensure!(!<UnOwner<T>>::exists(&unique_name), ERR_UN_ALREADY_CLAIMED);
+            ensure!(!Self::_has_name(&did), ERR_UN_ALREADY_SET);
+
+            let fee = Self::name_fee(unique_name.len());
+            Self::_pay_unique_name(sender.clone(), fee.clone())?;
+            Self::_assign_name(sender.clone(), &did, &unique_name, fee);
+
+            Self::deposit_event(RawEvent::NameUpdated(sender, did, unique_name, fee));
+            Ok(())
+        }
+
+        /// Release the unique name of a DID, part of the fee is refunded before the lease ends
+        pub fn release_unique_name(origin, did: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            Self::_check_did_ownership(sender.clone(), &did)?;
+            ensure!(Self::_has_name(&did), ERR_UN_NOT_SET);
+
+            let unique_name = Self::meta_of_did(&did).unique_name;
+            let refund = Self::_release_name(sender.clone(), &did);
 
+            Self::deposit_event(RawEvent::NameReleased(sender, did, unique_name, refund));
+            Ok(())
+        }
+
+        /// Replace the unique name of a DID, the old name is released
+        pub fn rename(origin, did: Vec<u8>, unique_name: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            Self::_check_did_ownership(sender.clone(), &did)?;
+            ensure!(Self::_has_name(&did), ERR_UN_NOT_SET);
+            ensure!(unique_name.len() as u64 <= Self::name_limit(), ERR_BYTEARRAY_LIMIT_NAME);
+            ensure!(!Self::is_short_name(&unique_name), ERR_UN_AUCTION);
             ensure!(!<UnOwner<T>>::exists(&unique_name), ERR_UN_ALREADY_CLAIMED);
-            
-            let length = unique_name.len() as u64;
-            let unused_charters = (BYTEARRAY_LIMIT_NAME as u64) - length;
-            let fee = T::Balance::sa(FEE_PER_USED_CHAR * (unused_charters + 1) * (unused_charters + 1));
+
+            // pay first, so nothing changes if the balance is too low
+            let fee = Self::name_fee(unique_name.len());
+            Self::_pay_unique_name(sender.clone(), fee.clone())?;
+
+            let old_name = Self::meta_of_did(&did).unique_name;
+            let refund = Self::_release_name(sender.clone(), &did);
+            Self::_assign_name(sender.clone(), &did, &unique_name, fee);
+
+            Self::deposit_event(RawEvent::NameReleased(sender.clone(), did.clone(), old_name, refund));
+            Self::deposit_event(RawEvent::NameUpdated(sender, did, unique_name, fee));
+            Ok(())
+        }
+
+        /// Extend the lease of a unique name, possible until the end of the grace period
+        pub fn renew_unique_name(origin, unique_name: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let owner = Self::owner_of_un(&unique_name).ok_or(ERR_UN_NOT_OWNER)?;
+            ensure!(owner == sender, ERR_UN_NOT_OWNER);
+
+            let fee = Self::name_fee(unique_name.len());
             Self::_pay_unique_name(sender.clone(), fee.clone())?;
 
+            let expiry = Self::expiry_of_un(&unique_name) + T::BlockNumber::sa(NAME_LEASE_PERIOD);
+            Self::_set_expiry(&unique_name, expiry);
+            <UnFee<T>>::insert(&unique_name, fee);
+
+            Self::deposit_event(RawEvent::NameRenewed(sender, unique_name, expiry, fee));
+            Ok(())
+        }
+
+        /// Point an owned unique name to another owned DID without a unique name
+        pub fn repoint_unique_name(origin, unique_name: Vec<u8>, did: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let owner = Self::owner_of_un(&unique_name).ok_or(ERR_UN_NOT_OWNER)?;
+            ensure!(owner == sender, ERR_UN_NOT_OWNER);
+            Self::_check_did_ownership(sender.clone(), &did)?;
+            ensure!(!Self::_has_name(&did), ERR_UN_ALREADY_SET);
+
+            Self::_detach_name(&unique_name);
+
             let mut metalog = Self::meta_of_did(&did);
             metalog.unique_name = unique_name.clone();
-
-            let meta_index = <OwnedMetaIndex<T>>::get(&did);
-            <OwnedMetaArray<T>>::insert((sender.clone(), meta_index -1), &metalog);
             <DidMeta<T>>::insert(&did, &metalog);
+            <UnMeta<T>>::insert(&unique_name, &did);
+
+            Self::deposit_event(RawEvent::NameRepointed(sender, unique_name, did));
+            Ok(())
+        }
 
-            <UnMeta<T>>::insert(&metalog.unique_name, &metalog);
-            <UnOwner<T>>::insert(&metalog.unique_name, &sender);
+        /// Hand a unique name to another account, the name is detached from its DID
+        /// until the receiver points it to one of their DIDs
+        pub fn transfer_unique_name(origin, unique_name: Vec<u8>, receiver: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
 
-            Self::deposit_event(RawEvent::NameUpdated(sender, did, unique_name, fee));
+            let owner = Self::owner_of_un(&unique_name).ok_or(ERR_UN_NOT_OWNER)?;
+            ensure!(owner == sender, ERR_UN_NOT_OWNER);
+
+            Self::_detach_name(&unique_name);
+            <UnOwner<T>>::insert(&unique_name, &receiver);
+
+            Self::deposit_event(RawEvent::NameTransferred(sender, receiver, unique_name));
+            Ok(())
+        }
+
+        /// Point a name in the sender's namespace to a DID, names are free of charge,
+        /// but a deposit is reserved until the name is removed
+        pub fn set_namespaced_name(origin, name: Vec<u8>, did: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(!name.is_empty() && name.len() as u64 <= Self::name_limit(), ERR_BYTEARRAY_LIMIT_NAME);
+            ensure!(<DidMeta<T>>::exists(&did), ERR_DID_NOT_EXIST);
+            ensure!(!<DeletedAt<T>>::exists(&did), ERR_DID_DELETED);
+
+            let key = (sender.clone(), name.clone());
+            if !<NsName<T>>::exists(&key) {
+                <balances::Module<T> as ReservableCurrency<_>>::reserve(&sender, T::Balance::sa(NAMESPACED_NAME_DEPOSIT))?;
+            }
+            <NsName<T>>::insert(&key, &did);
+
+            Self::deposit_event(RawEvent::NamespacedNameSet(sender, name, did));
+            Ok(())
+        }
+
+        /// Remove a name from the sender's namespace and return its deposit
+        pub fn remove_namespaced_name(origin, name: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            <NsName<T>>::take((sender.clone(), name.clone())).ok_or(ERR_UN_NOT_IN_NAMESPACE)?;
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&sender, T::Balance::sa(NAMESPACED_NAME_DEPOSIT));
+
+            Self::deposit_event(RawEvent::NamespacedNameRemoved(sender, name));
+            Ok(())
+        }
+
+        /// Rebuild the owned metalog index of an account stored by an earlier runtime version,
+        /// anyone can trigger it, otherwise it runs on the next change of the account
+        pub fn repair_owner_index(origin, owner: T::AccountId) -> Result {
+            let _ = ensure_signed(origin)?;
+            ensure!(!Self::index_repaired(&owner), ERR_INDEX_REPAIRED);
+
+            Self::_repair_owner_index(&owner);
             Ok(())
         }
 
         /// Change license code
+        /// The delete license code tombstones the DID and releases its unique name
         pub fn change_license_code(origin, did: Vec<u8>, license_code: u16)-> Result{
             let sender = ensure_signed(origin)?;
 
             Self::_check_did_ownership(sender.clone(), &did)?;
+            Self::_check_license(license_code)?;
+            if license_code == DELETE_LICENSE {
+                return Self::_delete_request(sender, &did);
+            }
+
             let mut metadata = Self::meta_of_did(&did);
             metadata.license_code = license_code.clone();
 
-            let meta_index = <OwnedMetaIndex<T>>::get(&did);
-            <OwnedMetaArray<T>>::insert((sender.clone(), meta_index -1), &metadata);
             <DidMeta<T>>::insert(&did, &metadata);
 
             Self::deposit_event(RawEvent::LicenseUpdated(sender, did, license_code));
             Ok(())
         }
+
+        /// Register a new license code, only root or higher federation ranks
+        pub fn register_license(
+            origin,
+            license_code: u16,
+            spdx: Vec<u8>,
+            name: Vec<u8>,
+            terms_hash: Vec<u8>,
+            commercial_use: bool) -> Result {
+
+            <federation::Module<T>>::ensure_root_or_rank(origin, LICENSE_REGISTRATION_RANK)?;
+
+            ensure!(license_code != NO_LICENSE && license_code != DELETE_LICENSE, ERR_LICENSE_INVALID);
+            ensure!(!<Licenses<T>>::exists(license_code), ERR_LICENSE_ALREADY_REGISTERED);
+            ensure!(spdx.len() <= BYTEARRAY_LIMIT_LICENSE, ERR_BYTEARRAY_LIMIT_LICENSE);
+            ensure!(name.len() <= BYTEARRAY_LIMIT_LICENSE, ERR_BYTEARRAY_LIMIT_LICENSE);
+            ensure!(terms_hash.len() <= BYTEARRAY_LIMIT_HASH, ERR_BYTEARRAY_LIMIT_HASH);
+
+            let license = License {
+                spdx,
+                name,
+                terms_hash,
+                commercial_use,
+            };
+            <Licenses<T>>::insert(license_code, &license);
+
+            Self::deposit_event(RawEvent::LicenseRegistered(license_code, license.spdx));
+            Ok(())
+        }
+
+        /// Change the fee per unused character of unique names
+        pub fn set_fee_per_used_char(origin, fee: u64) -> Result {
+            <federation::Module<T>>::ensure_root_or_rank(origin, PARAMETER_RANK)?;
+
+            <FeePerUsedChar<T>>::put(fee);
+            Self::deposit_event(RawEvent::FeePerUsedCharUpdated(fee));
+            Ok(())
+        }
+
+        /// Change the maximum length of unique names
+        pub fn set_name_limit(origin, limit: u64) -> Result {
+            <federation::Module<T>>::ensure_root_or_rank(origin, PARAMETER_RANK)?;
+
+            ensure!(limit > 0 && limit <= MAX_NAME_LIMIT, ERR_PARAMETER_INVALID);
+            ensure!(limit >= Self::short_name_length(), ERR_PARAMETER_INVALID);
+            <NameLimit<T>>::put(limit);
+            Self::deposit_event(RawEvent::NameLimitUpdated(limit));
+            Ok(())
+        }
+
+        /// Change the length below which unique names are auctioned
+        pub fn set_short_name_length(origin, length: u64) -> Result {
+            <federation::Module<T>>::ensure_root_or_rank(origin, PARAMETER_RANK)?;
+
+            ensure!(length <= Self::name_limit(), ERR_PARAMETER_INVALID);
+            <ShortNameLength<T>>::put(length);
+            Self::deposit_event(RawEvent::ShortNameLengthUpdated(length));
+            Ok(())
+        }
     }
 }
 
 decl_event!(
 	pub enum Event<T> where
         <T as system::Trait>::AccountId,
+        <T as system::Trait>::BlockNumber,
         <T as timestamp::Trait>::Moment, 
         <T as balances::Trait>::Balance 
     {
-        Stored(AccountId, Moment, Vec<u8>),
+        /// owner, time, did, file hash, price, metadata hash, provider id
+        Stored(AccountId, Moment, Vec<u8>, Vec<u8>, Balance, Vec<u8>, Option<u64>),
 		TransferOwnership(AccountId, AccountId, Vec<u8>),
 		LicenseUpdated(AccountId, Vec<u8>,u16),
+        /// license code, SPDX identifier
+        LicenseRegistered(u16, Vec<u8>),
+        FeePerUsedCharUpdated(u64),
+        NameLimitUpdated(u64),
+        ShortNameLengthUpdated(u64),
 		NameUpdated(AccountId, Vec<u8>,Vec<u8>, Balance),
+        /// owner, unique name, new lease end, fee
+        NameRenewed(AccountId, Vec<u8>, BlockNumber, Balance),
+        /// unique name, which is available again
+        NameExpired(Vec<u8>),
+        /// owner, unique name, new did
+        NameRepointed(AccountId, Vec<u8>, Vec<u8>),
+        /// sender, receiver, unique name
+        NameTransferred(AccountId, AccountId, Vec<u8>),
+        /// owner, did, released unique name, refund, also emitted without refund for delete requests
+        NameReleased(AccountId, Vec<u8>, Vec<u8>, Balance),
+        /// owner, did, block of the request, pinning services should unpin the content
+        DeleteRequested(AccountId, Vec<u8>, BlockNumber),
+        /// owner, number of owned metalogs after the repair
+        OwnerIndexRepaired(AccountId, u64),
+        /// publisher, name in the publisher's namespace, did
+        NamespacedNameSet(AccountId, Vec<u8>, Vec<u8>),
+        /// publisher, removed name
+        NamespacedNameRemoved(AccountId, Vec<u8>),
+        /// owner, did, new provider id
+        ProviderChanged(AccountId, Vec<u8>, Option<u64>),
+        /// creator, did, royalty
+        RoyaltyPaid(AccountId, Vec<u8>, Balance),
 	}
 );
 
 impl<T: Trait> Module<T> {
+    /// SCALE encoded payload a publisher signs to let a third party relay the metalog
+    pub fn metalog_payload(
+        did: &Vec<u8>,
+        license_code: u16,
+        file_hash: &Vec<u8>,
+        price: &T::Balance,
+        meta_hash: &Vec<u8>,
+        provider: &Option<u64>,
+        royalty: u16,
+    ) -> Vec<u8> {
+        (did, license_code, file_hash, price, meta_hash, provider, royalty).encode()
+    }
+
+    /// Metalog of the DID at the index of the owner's array
+    pub fn metadata_of_owner_by_index(key: (T::AccountId, u64)) -> Metalog<T::AccountId, T::Moment, T::Balance> {
+        Self::meta_of_did(Self::did_of_owner_by_index(key))
+    }
+
+    /// Metalog of the DID, records in the legacy layout are converted without a file hash, price,
+    /// metadata hash, provider or royalty and with the current owner as creator
+    pub fn meta_of_did<K: Borrow<Vec<u8>>>(did: K) -> Metalog<T::AccountId, T::Moment, T::Balance> {
+        let did = did.borrow();
+        let mut key = b"Metalog DidMeta".to_vec();
+        did.encode_to(&mut key);
+        let encoded = match runtime_io::storage(&runtime_io::blake2_256(&key)) {
+            Some(encoded) => encoded,
+            None => return Metalog::default(),
+        };
+
+        // the legacy layout ends right after the timestamp, the current one is always longer
+        let mut input = &encoded[..];
+        if let Some(legacy) = LegacyMetalog::<T::Moment>::decode(&mut input) {
+            if input.is_empty() {
+                return Metalog {
+                    did: legacy.did,
+                    unique_name: legacy.unique_name,
+                    license_code: legacy.license_code,
+                    file_hash: Vec::new(),
+                    price: T::Balance::sa(0),
+                    meta_hash: Vec::new(),
+                    provider: None,
+                    creator: Self::owner_of_did(did).unwrap_or_default(),
+                    royalty: 0,
+                    time: legacy.time,
+                };
+            }
+        }
+        Metalog::decode(&mut &encoded[..]).unwrap_or_default()
+    }
+
+    /// Metalog of the DID the unique name points to
+    pub fn meta_of_un(unique_name: &Vec<u8>) -> Metalog<T::AccountId, T::Moment, T::Balance> {
+        Self::meta_of_did(Self::did_of_un(unique_name))
+    }
+
+    /// Metalog of the DID, if it exists
+    pub fn metalog_by_did(did: &Vec<u8>) -> Option<Metalog<T::AccountId, T::Moment, T::Balance>> {
+        if !<DidMeta<T>>::exists(did) {
+            return None;
+        }
+        Some(Self::meta_of_did(did))
+    }
+
+    /// Metalog the unique name points to, if it is attached to a DID
+    pub fn metalog_by_name(unique_name: &Vec<u8>) -> Option<Metalog<T::AccountId, T::Moment, T::Balance>> {
+        if !<UnMeta<T>>::exists(unique_name) {
+            return None;
+        }
+        Some(Self::meta_of_un(unique_name))
+    }
+
+    /// Page of the owner's metalogs, at most MAX_PAGE_SIZE records from the start index
+    pub fn metalogs_of_owner(owner: &T::AccountId, start: u64, limit: u64) -> Vec<Metalog<T::AccountId, T::Moment, T::Balance>> {
+        let end = start
+            .saturating_add(limit.min(MAX_PAGE_SIZE))
+            .min(Self::owner_meta_count(owner));
+        (start..end)
+            .map(|index| Self::metadata_of_owner_by_index((owner.clone(), index)))
+            .collect()
+    }
+
+    /// Page of all claimed unique names, at most MAX_PAGE_SIZE names from the start index
+    pub fn unique_names(start: u64, limit: u64) -> Vec<Vec<u8>> {
+        let end = start
+            .saturating_add(limit.min(MAX_PAGE_SIZE))
+            .min(Self::un_count());
+        (start..end).map(|index| Self::un_by_index(index)).collect()
+    }
+
+    /// Winning record of a name for a consumer, who trusts the publishers in the given rank order
+    /// A publisher's record is the DID named in their namespace, otherwise the unique name, if it points
+    /// to a DID they created. Like in follow lists, the creator stays the publisher after the DID is sold.
+    /// The highest ranked publisher with a record wins, deleted DIDs are skipped.
+    pub fn resolve_name(
+        publishers: &[T::AccountId],
+        name: &Vec<u8>,
+    ) -> Option<(T::AccountId, Metalog<T::AccountId, T::Moment, T::Balance>)> {
+        let unique_did = if <UnMeta<T>>::exists(name) {
+            let did = Self::did_of_un(name);
+            Some((Self::meta_of_did(&did).creator, did))
+        } else {
+            None
+        };
+        publishers.iter().find_map(|publisher| {
+            let did = match (Self::did_of_ns_name((publisher.clone(), name.clone())), &unique_did) {
+                (Some(did), _) => did,
+                (None, Some((creator, did))) if creator == publisher => did.clone(),
+                (None, _) => return None,
+            };
+            if <DeletedAt<T>>::exists(&did) {
+                return None;
+            }
+            Self::metalog_by_did(&did).map(|metalog| (publisher.clone(), metalog))
+        })
+    }
+
+    /// Fee of a unique name, none if the name can't be bought with buy_unique_name
+    pub fn name_price(unique_name: &Vec<u8>) -> Option<T::Balance> {
+        if unique_name.is_empty()
+            || unique_name.len() as u64 > Self::name_limit()
+            || Self::is_short_name(unique_name)
+            || <UnOwner<T>>::exists(unique_name)
+        {
+            return None;
+        }
+        Some(Self::name_fee(unique_name.len()))
+    }
+
     /// store metalog
-    fn _owner_store(sender: T::AccountId, metalog: Metalog<T::Moment>) -> Result {
+    fn _owner_store(sender: T::AccountId, metalog: Metalog<T::AccountId, T::Moment, T::Balance>) -> Result {
+        Self::_ensure_index_repaired(&sender);
+
         let count = Self::owner_meta_count(&sender);
         let updated_count = count.checked_add(1).ok_or(ERR_OVERFLOW)?;
 
-        <OwnedMetaArray<T>>::insert((sender.clone(), count), &metalog);
+        <OwnedMetaArray<T>>::insert((sender.clone(), count), &metalog.did);
         <OwnedMetaCount<T>>::insert(&sender, updated_count);
-        <OwnedMetaIndex<T>>::insert(&metalog.did, updated_count);
+        <OwnedMetaIndex<T>>::insert(&metalog.did, count);
 
         <DidMeta<T>>::insert(&metalog.did, &metalog);
         <DidOwner<T>>::insert(&metalog.did, &sender);
@@ -228,173 +782,673 @@ impl<T: Trait> Module<T> {
         ensure!(<DidMeta<T>>::exists(did), ERR_DID_NOT_EXIST);
         let owner = Self::owner_of_did(did).ok_or(ERR_DID_NO_OWNER)?;
         ensure!(owner == sender, ERR_NOT_OWNER);
+        ensure!(!<DeletedAt<T>>::exists(did), ERR_DID_DELETED);
 
         Ok(())
     }
 
-    /// Transfer ownership
-    fn _transfer(sender: T::AccountId, receiver: T::AccountId, did: &Vec<u8>) -> Result {
-        let receiver_total_count = Self::owner_meta_count(&receiver);
-        let new_receiver_count = receiver_total_count.checked_add(1).ok_or(ERR_OVERFLOW)?;
-
-        let sender_total_count = Self::owner_meta_count(&sender);
-        let new_sender_count = sender_total_count.checked_sub(1).ok_or(ERR_UNDERFLOW)?;
+    /// Checks that a referenced pinning provider is active
+    fn _check_provider(provider: Option<u64>) -> Result {
+        if let Some(id) = provider {
+            ensure!(<providers::Module<T>>::is_active(id), ERR_PROVIDER_NOT_ACTIVE);
+        }
+        Ok(())
+    }
 
-        let meta_index = <OwnedMetaIndex<T>>::get(did);
-        let meta_object = <OwnedMetaArray<T>>::get((sender.clone(), new_sender_count));
+    /// Checks that the license code is built in or registered
+    fn _check_license(license_code: u16) -> Result {
+        ensure!(
+            license_code == NO_LICENSE || license_code == DELETE_LICENSE || <Licenses<T>>::exists(license_code),
+            ERR_LICENSE_NOT_REGISTERED
+        );
+        Ok(())
+    }
 
-        if meta_index != new_sender_count {
-            <OwnedMetaArray<T>>::insert((sender.clone(), meta_index), &meta_object);
-            <OwnedMetaIndex<T>>::insert(&meta_object.did, meta_index);
-        }
+    /// Tombstones the DID and releases its unique name
+    fn _delete_request(sender: T::AccountId, did: &Vec<u8>) -> Result {
+        let mut metalog = Self::meta_of_did(did);
 
-        // if un is not the default un
         let mut default_name = Vec::new();
         default_name.push(0);
-        if meta_object.unique_name != default_name {
-            <UnOwner<T>>::insert(did, &receiver);
+        let released_name = if metalog.unique_name != default_name {
+            Self::_remove_name(&metalog.unique_name);
+            Some(metalog.unique_name.clone())
+        } else {
+            None
+        };
+        metalog.unique_name = default_name;
+        metalog.license_code = DELETE_LICENSE;
+
+        <DidMeta<T>>::insert(did, &metalog);
+        if let Some(unique_name) = released_name {
+            // the fee of the remaining lease isn't refunded
+            Self::deposit_event(RawEvent::NameReleased(sender.clone(), did.clone(), unique_name, T::Balance::sa(0)));
         }
 
-        <DidOwner<T>>::insert(did, &receiver);
+        let block_number = <system::Module<T>>::block_number();
+        <DeletedAt<T>>::insert(did, block_number);
+        T::OnDeleteRequest::on_delete_request(did);
+
+        Self::deposit_event(RawEvent::DeleteRequested(sender, did.clone(), block_number));
+        Ok(())
+    }
+
+    /// Fee for a unique name, shorter names are more expensive
+    pub fn name_fee(length: usize) -> T::Balance {
+        // names above a lowered limit are priced like names at the limit
+        let unused_charters = Self::name_limit().saturating_sub(length as u64);
+        let factor = (unused_charters + 1).saturating_mul(unused_charters + 1);
+        T::Balance::sa(Self::fee_per_used_char().saturating_mul(factor))
+    }
 
-        <OwnedMetaIndex<T>>::insert(did, receiver_total_count);
+    /// Whether the unique name needs to be bought in a name auction
+    pub fn is_short_name(unique_name: &Vec<u8>) -> bool {
+        (unique_name.len() as u64) < Self::short_name_length()
+    }
 
-        <OwnedMetaArray<T>>::remove((sender.clone(), new_sender_count));
-        <OwnedMetaArray<T>>::insert((receiver.clone(), receiver_total_count), meta_object);
+    /// Assigns an auctioned unique name to the winner
+    /// The name is detached until the winner points it to one of their DIDs
+    pub fn claim_auctioned_name(owner: T::AccountId, unique_name: Vec<u8>, fee: T::Balance) -> Result {
+        ensure!(!<UnOwner<T>>::exists(&unique_name), ERR_UN_ALREADY_CLAIMED);
 
-        <OwnedMetaCount<T>>::insert(&sender, new_sender_count);
-        <OwnedMetaCount<T>>::insert(&receiver, new_receiver_count);
+        <UnOwner<T>>::insert(&unique_name, &owner);
+        <UnFee<T>>::insert(&unique_name, fee);
+        Self::_list_name(&unique_name);
 
+        let expiry = <system::Module<T>>::block_number() + T::BlockNumber::sa(NAME_LEASE_PERIOD);
+        Self::_set_expiry(&unique_name, expiry);
         Ok(())
     }
 
-    /// Payment for unique names
-    fn _pay_unique_name(who: T::AccountId, fee: T::Balance) -> Result {
+    /// Checks that the account owns the DID and that it hasn't been deleted
+    pub fn check_ownership(who: &T::AccountId, did: &Vec<u8>) -> Result {
+        Self::_check_did_ownership(who.clone(), did)
+    }
+
+    /// Checks without changes, that the owner can transfer the DID to the receiver
+    pub fn check_transfer(owner: &T::AccountId, receiver: &T::AccountId, did: &Vec<u8>) -> Result {
+        Self::_check_did_ownership(owner.clone(), did)?;
+        ensure!(owner != receiver, ERR_TRANSFER_SELF);
+        ensure!(Self::owner_meta_count(receiver).checked_add(1).is_some(), ERR_OVERFLOW);
+        Ok(())
+    }
+
+    /// Transfers a DID sold by the marketplace, the buyer has already paid
+    pub fn transfer_sold(seller: T::AccountId, buyer: T::AccountId, did: &Vec<u8>) -> Result {
+        Self::_check_did_ownership(seller.clone(), did)?;
+        Self::_transfer(seller.clone(), buyer.clone(), did)?;
+
+        Self::deposit_event(RawEvent::TransferOwnership(seller, buyer, did.clone()));
+        Ok(())
+    }
+
+    /// Pays the price of a sale of the DID or its license from the buyer's balance
+    /// The royalty goes to the creator, unless the creator sells, the rest to the seller.
+    /// A royalty below the existential deposit of the creator's reaped or new account stays with the seller.
+    pub fn pay_sale(buyer: &T::AccountId, seller: &T::AccountId, did: &Vec<u8>, price: T::Balance) -> Result {
+        let metalog = Self::meta_of_did(did);
+        let mut royalty = if metalog.creator == *seller {
+            T::Balance::sa(0)
+        } else {
+            // split to avoid an overflow of large prices
+            let per_mill = T::Balance::sa(metalog.royalty as u64);
+            let thousand = T::Balance::sa(1000);
+            price / thousand * per_mill + price % thousand * per_mill / thousand
+        };
+        if !Self::_can_receive(&metalog.creator, royalty) {
+            royalty = T::Balance::sa(0);
+        }
+        ensure!(Self::_can_receive(seller, price - royalty), ERR_SELLER_SHARE_LOW);
+
         let _ = <balances::Module<T> as Currency<_>>::withdraw(
-            &who,
-            fee,
-            WithdrawReason::Fee,
+            buyer,
+            price,
+            WithdrawReason::Transfer,
             ExistenceRequirement::KeepAlive,
         )?;
+        // both deposits create or top up an account, so nothing is burned as dust
+        if royalty > T::Balance::sa(0) {
+            let _ = <balances::Module<T> as Currency<_>>::deposit_creating(&metalog.creator, royalty);
+            Self::deposit_event(RawEvent::RoyaltyPaid(metalog.creator, did.clone(), royalty));
+        }
+        let _ = <balances::Module<T> as Currency<_>>::deposit_creating(seller, price - royalty);
         Ok(())
     }
-}
 
-/// tests for this module
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Whether a deposit keeps or creates the account, deposits below the existential deposit of a new account are lost
+    fn _can_receive(who: &T::AccountId, amount: T::Balance) -> bool {
+        <balances::Module<T>>::free_balance(who) > T::Balance::sa(0)
+            || amount >= <balances::Module<T>>::existential_deposit()
+    }
 
-    use primitives::{Blake2Hasher, H256};
-    use runtime_io::with_externalities;
-    use runtime_primitives::{
-        testing::{Digest, DigestItem, Header},
-        traits::{BlakeTwo256, IdentityLookup},
-        BuildStorage,
-    };
-    use support::{assert_noop, assert_ok, impl_outer_origin};
+    /// Block at which an unrenewed unique name is released
+    fn _release_block(unique_name: &Vec<u8>) -> T::BlockNumber {
+        Self::expiry_of_un(unique_name) + T::BlockNumber::sa(NAME_GRACE_PERIOD)
+    }
 
-    const ERR_BALANCE_LOW: &str = "too few free funds in account";
+    /// Stores the lease end and schedules the release of the unique name
+    fn _set_expiry(unique_name: &Vec<u8>, expiry: T::BlockNumber) {
+        <UnExpiry<T>>::insert(unique_name, expiry);
+        let release_block = Self::_release_block(unique_name);
+        <UnRelease<T>>::mutate(release_block, |names| names.push(unique_name.clone()));
+    }
 
-    impl_outer_origin! {
-        pub enum Origin for Test {}
+    /// Removes the unique name entries, scheduled releases are skipped afterwards
+    fn _remove_name(unique_name: &Vec<u8>) {
+        Self::_unlist_name(unique_name);
+        <UnMeta<T>>::remove(unique_name);
+        <UnOwner<T>>::remove(unique_name);
+        <UnExpiry<T>>::remove(unique_name);
+        <UnFee<T>>::remove(unique_name);
     }
 
-    #[derive(Clone, Eq, PartialEq)]
-    pub struct Test;
+    /// Adds the unique name to the array of all names, if it isn't listed yet
+    fn _list_name(unique_name: &Vec<u8>) {
+        let count = Self::un_count();
+        let index = <UnIndex<T>>::get(unique_name);
+        if index < count && Self::un_by_index(index) == *unique_name {
+            return;
+        }
+        <UnArray<T>>::insert(count, unique_name);
+        <UnIndex<T>>::insert(unique_name, count);
+        <UnCount<T>>::put(count + 1);
+    }
 
-    impl system::Trait for Test {
-        type Origin = Origin;
-        type Index = u64;
-        type BlockNumber = u64;
-        type Hash = H256;
-        type Hashing = BlakeTwo256;
-        type Digest = Digest;
-        type AccountId = u64;
-        type Lookup = IdentityLookup<Self::AccountId>;
-        type Header = Header;
-        type Event = ();
-        type Log = DigestItem;
+    /// Removes the unique name from the array of all names, the last name takes its place
+    fn _unlist_name(unique_name: &Vec<u8>) {
+        let count = Self::un_count();
+        let index = <UnIndex<T>>::get(unique_name);
+        if index >= count || Self::un_by_index(index) != *unique_name {
+            return;
+        }
+        let last_index = count - 1;
+        if index != last_index {
+            let last_name = <UnArray<T>>::get(last_index);
+            <UnArray<T>>::insert(index, &last_name);
+            <UnIndex<T>>::insert(&last_name, index);
+        }
+        <UnArray<T>>::remove(last_index);
+        <UnIndex<T>>::remove(unique_name);
+        <UnCount<T>>::put(last_index);
     }
-    impl balances::Trait for Test {
-        type Balance = u64;
-        type OnFreeBalanceZero = ();
-        type OnNewAccount = ();
-        type Event = ();
-        type TransactionPayment = ();
-        type DustRemoval = ();
-        type TransferPayment = ();
+
+    /// Resets the name of the DID the unique name currently points to
+    fn _detach_name(unique_name: &Vec<u8>) {
+        if !<UnMeta<T>>::exists(unique_name) {
+            return;
+        }
+        let did = <UnMeta<T>>::take(unique_name);
+
+        if <DidMeta<T>>::exists(&did) {
+            let mut metalog = Self::meta_of_did(&did);
+            let mut default_name = Vec::new();
+            default_name.push(0);
+            metalog.unique_name = default_name;
+
+            <DidMeta<T>>::insert(&did, &metalog);
+        }
     }
 
-    impl timestamp::Trait for Test {
-        type Moment = u64;
-        type OnTimestampSet = ();
+    /// Whether the DID has a unique name other than the default one
+    fn _has_name(did: &Vec<u8>) -> bool {
+        let mut default_name = Vec::new();
+        default_name.push(0);
+        let unique_name = Self::meta_of_did(did).unique_name;
+        unique_name != default_name && !unique_name.is_empty()
     }
 
-    impl Trait for Test {
-        type Event = ();
+    /// Sets the unique name of a DID and starts a new lease
+    fn _assign_name(owner: T::AccountId, did: &Vec<u8>, unique_name: &Vec<u8>, fee: T::Balance) {
+        let mut metalog = Self::meta_of_did(did);
+        metalog.unique_name = unique_name.clone();
+
+        <DidMeta<T>>::insert(did, &metalog);
+
+        <UnMeta<T>>::insert(unique_name, did);
+        <UnOwner<T>>::insert(unique_name, &owner);
+        <UnFee<T>>::insert(unique_name, fee);
+        Self::_list_name(unique_name);
+
+        let expiry = <system::Module<T>>::block_number() + T::BlockNumber::sa(NAME_LEASE_PERIOD);
+        Self::_set_expiry(unique_name, expiry);
     }
 
-    type Balances = balances::Module<Test>;
-    type Metalog = Module<Test>;
+    /// Frees the unique name of a DID and refunds part of the fee, if the lease hasn't ended
+    fn _release_name(owner: T::AccountId, did: &Vec<u8>) -> T::Balance {
+        let mut metalog = Self::meta_of_did(did);
+        let unique_name = metalog.unique_name.clone();
 
-    fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
-        let mut t = system::GenesisConfig::<Test>::default()
-            .build_storage()
-            .unwrap()
-            .0;
-        t.extend(
-            balances::GenesisConfig::<Test>::default()
-                .build_storage()
-                .unwrap()
-                .0,
-        );
-        t.extend(
-            GenesisConfig::<Test> {
-                metalog: vec![(0, 0)],
-            }
-            .build_storage()
-            .unwrap()
-            .0,
-        );
-        t.into()
+        let mut refund = T::Balance::sa(0);
+        if <system::Module<T>>::block_number() < Self::expiry_of_un(&unique_name) {
+            // divided first, fees saturated at the maximum would overflow
+            refund = T::Balance::sa(Self::fee_of_un(&unique_name).as_() / 100 * NAME_REFUND_PERCENT);
+        }
+        Self::_remove_name(&unique_name);
+
+        let mut default_name = Vec::new();
+        default_name.push(0);
+        metalog.unique_name = default_name;
+        <DidMeta<T>>::insert(did, &metalog);
+
+        <treasury::Module<T>>::refund(&owner, refund)
     }
 
-    #[test]
-    fn create_metalog_works() {
-        with_externalities(&mut new_test_ext(), || {
-            let did_new = vec![1, 2];
-            let did_claimed = vec![1, 2, 3];
-            let mut did_too_long = did_new.clone();
-            for _i in 1..100 {
-                did_too_long.push(2);
+    /// Releases an expired unique name and resets the name of its DID
+    fn _expire_name(unique_name: &Vec<u8>) {
+        Self::_detach_name(unique_name);
+        Self::_remove_name(unique_name);
+
+        Self::deposit_event(RawEvent::NameExpired(unique_name.clone()));
+    }
+
+    /// Position of the DID in the array of the owner, if the entry at its index is the DID
+    fn _owned_index(owner: &T::AccountId, did: &Vec<u8>) -> Option<u64> {
+        if !Self::index_repaired(owner) {
+            return None;
+        }
+        let index = <OwnedMetaIndex<T>>::get(did);
+        if index < Self::owner_meta_count(owner)
+            && Self::did_of_owner_by_index((owner.clone(), index)) == *did
+        {
+            return Some(index);
+        }
+        None
+    }
+
+    /// Appends the DID to the array of the owner, if it isn't listed yet
+    fn _list_owned(owner: &T::AccountId, did: &Vec<u8>) {
+        if Self::_owned_index(owner, did).is_some() {
+            return;
+        }
+        let count = Self::owner_meta_count(owner);
+        <OwnedMetaArray<T>>::insert((owner.clone(), count), did);
+        <OwnedMetaIndex<T>>::insert(did, count);
+        <OwnedMetaCount<T>>::insert(owner, count + 1);
+    }
+
+    /// Repairs the index of the account on its first change after the upgrade
+    fn _ensure_index_repaired(owner: &T::AccountId) {
+        if Self::index_repaired(owner) {
+            return;
+        }
+        if Self::owner_meta_count(owner) == 0 {
+            <IndexRepaired<T>>::insert(owner, true);
+            return;
+        }
+        Self::_repair_owner_index(owner);
+    }
+
+    /// Migration of the owned metalog index. Earlier versions stored a 1-based index,
+    /// moved the last record of the sender instead of the transferred one
+    /// and stored the new owner of a named DID under the DID instead of the name.
+    /// The array is rebuilt from DidMeta and DidOwner, records of DIDs owned by
+    /// someone else are handed to the array of their owner.
+    /// Metalogs of the rebuilt array are stored in the current layout.
+    fn _repair_owner_index(owner: &T::AccountId) {
+        let count = Self::owner_meta_count(owner);
+        let mut owned: Vec<Vec<u8>> = Vec::new();
+        for index in 0..count {
+            if !<OwnedMetaArray<T>>::exists((owner.clone(), index)) {
+                continue;
             }
-            assert_noop!(
-                Metalog::create_metalog(
-                    Origin::signed(20),
-                    did_new.clone(),
-                    DELETE_LICENSE
-                ),
-                ERR_LICENSE_INVALID
-            );
-            assert_noop!(
-                Metalog::create_metalog(Origin::signed(20), did_claimed, 0),
+            let did = <OwnedMetaArray<T>>::take((owner.clone(), index));
+
+            if !<UnMeta<T>>::exists(&did) && !<UnExpiry<T>>::exists(&did) {
+                <UnOwner<T>>::remove(&did);
+            }
+
+            match Self::owner_of_did(&did) {
+                Some(ref did_owner) if did_owner == owner => {
+                    if !owned.contains(&did) {
+                        owned.push(did);
+                    }
+                }
+                Some(did_owner) => Self::_list_owned(&did_owner, &did),
+                None => {}
+            }
+        }
+
+        for (index, did) in owned.iter().enumerate() {
+            <OwnedMetaArray<T>>::insert((owner.clone(), index as u64), did);
+            <OwnedMetaIndex<T>>::insert(did, index as u64);
+            // stores metalogs of the legacy layout in the current one, with the owner as creator
+            let metalog = Self::meta_of_did(did);
+            <DidMeta<T>>::insert(did, &metalog);
+            // unique names follow their DID
+            if Self::_has_name(did) {
+                <UnOwner<T>>::insert(&metalog.unique_name, owner);
+                Self::_list_name(&metalog.unique_name);
+            }
+        }
+        let repaired_count = owned.len() as u64;
+        <OwnedMetaCount<T>>::insert(owner, repaired_count);
+        <IndexRepaired<T>>::insert(owner, true);
+
+        Self::deposit_event(RawEvent::OwnerIndexRepaired(owner.clone(), repaired_count));
+    }
+
+    /// Transfer ownership
+    /// The last record of the sender takes the place of the transferred one
+    fn _transfer(sender: T::AccountId, receiver: T::AccountId, did: &Vec<u8>) -> Result {
+        ensure!(sender != receiver, ERR_TRANSFER_SELF);
+        Self::_ensure_index_repaired(&sender);
+        Self::_ensure_index_repaired(&receiver);
+        // lists a DID, which is still in the array of an unrepaired account
+        Self::_list_owned(&sender, did);
+
+        let sender_count = Self::owner_meta_count(&sender);
+        let last_index = sender_count.checked_sub(1).ok_or(ERR_UNDERFLOW)?;
+        let receiver_count = Self::owner_meta_count(&receiver);
+        let new_receiver_count = receiver_count.checked_add(1).ok_or(ERR_OVERFLOW)?;
+
+        let meta_index = Self::_owned_index(&sender, did).ok_or(ERR_INDEX_CORRUPTED)?;
+        ensure!(meta_index <= last_index, ERR_INDEX_CORRUPTED);
+
+        if meta_index != last_index {
+            let last_did = <OwnedMetaArray<T>>::get((sender.clone(), last_index));
+            <OwnedMetaArray<T>>::insert((sender.clone(), meta_index), &last_did);
+            <OwnedMetaIndex<T>>::insert(&last_did, meta_index);
+        }
+        <OwnedMetaArray<T>>::remove((sender.clone(), last_index));
+        <OwnedMetaCount<T>>::insert(&sender, last_index);
+
+        <OwnedMetaArray<T>>::insert((receiver.clone(), receiver_count), did);
+        <OwnedMetaIndex<T>>::insert(did, receiver_count);
+        <OwnedMetaCount<T>>::insert(&receiver, new_receiver_count);
+
+        <DidOwner<T>>::insert(did, &receiver);
+
+        // the unique name moves with its DID
+        if Self::_has_name(did) {
+            <UnOwner<T>>::insert(Self::meta_of_did(did).unique_name, &receiver);
+        }
+
+        Ok(())
+    }
+
+    /// Payment for unique names, the fee goes to the treasury
+    fn _pay_unique_name(who: T::AccountId, fee: T::Balance) -> Result {
+        <treasury::Module<T>>::collect_fee(&who, fee)
+    }
+}
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock::{self, Origin, Test, TestSignature};
+    use primitives::Blake2Hasher;
+    use proptest::prelude::*;
+    use runtime_io::with_externalities;
+    use runtime_primitives::traits::OnFinalize;
+    use support::{assert_noop, assert_ok};
+
+    const ERR_BALANCE_LOW: &str = "too few free funds in account";
+
+    type Balances = balances::Module<Test>;
+    type System = system::Module<Test>;
+    type Treasury = treasury::Module<Test>;
+    type Providers = providers::Module<Test>;
+    type Metalog = Module<Test>;
+
+    /// Test externalities with the DID `[1, 2, 3]` of account 0
+    fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+        let mut t = mock::new_test_ext();
+        with_externalities(&mut t, || mock::create(0, vec![1, 2, 3]));
+        t
+    }
+
+    /// Storage key of a map entry
+    fn map_key<K: Encode>(prefix: &[u8], key: &K) -> [u8; 32] {
+        let mut map_key = prefix.to_vec();
+        key.encode_to(&mut map_key);
+        runtime_io::blake2_256(&map_key)
+    }
+
+    /// Metalog in the layout of spec version 3 and earlier
+    fn legacy(did: &Vec<u8>, unique_name: Vec<u8>) -> Vec<u8> {
+        LegacyMetalog {
+            did: did.clone(),
+            unique_name,
+            license_code: 0,
+            time: 7u64,
+        }
+        .encode()
+    }
+
+    /// Stores a metalog in the layout of spec version 3 and earlier
+    fn insert_legacy(did: &Vec<u8>, unique_name: Vec<u8>) {
+        runtime_io::set_storage(&map_key(b"Metalog DidMeta", did), &legacy(did, unique_name));
+    }
+
+    #[test]
+    fn create_metalog_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did_new = vec![1, 2];
+            let did_claimed = vec![1, 2, 3];
+            let file_hash = vec![5, 6, 7];
+            let meta_hash = vec![8, 9];
+            let provider = Some(0);
+            let mut did_too_long = did_new.clone();
+            for _i in 1..100 {
+                did_too_long.push(2);
+            }
+            let mut hash_too_long = file_hash.clone();
+            for _i in 1..100 {
+                hash_too_long.push(2);
+            }
+            assert_noop!(
+                Metalog::create_metalog(
+                    Origin::signed(20),
+                    did_new.clone(),
+                    DELETE_LICENSE,
+                    file_hash.clone(),
+                    10,
+                    meta_hash.clone(),
+                    provider,
+                    0,
+                    None
+                ),
+                ERR_LICENSE_INVALID
+            );
+            assert_noop!(
+                Metalog::create_metalog(
+                    Origin::signed(20),
+                    did_claimed,
+                    0,
+                    file_hash.clone(),
+                    10,
+                    meta_hash.clone(),
+                    provider,
+                    0,
+                    None
+                ),
                 ERR_DID_ALREADY_CLAIMED
             );
             assert_noop!(
                 Metalog::create_metalog(
                     Origin::signed(20),
                     did_too_long.clone(),
-                    0
+                    0,
+                    file_hash.clone(),
+                    10,
+                    meta_hash.clone(),
+                    provider,
+                    0,
+                    None
                 ),
                 ERR_BYTEARRAY_LIMIT_DID
             );
+            assert_noop!(
+                Metalog::create_metalog(
+                    Origin::signed(20),
+                    did_new.clone(),
+                    0,
+                    hash_too_long,
+                    10,
+                    meta_hash.clone(),
+                    provider,
+                    0,
+                    None
+                ),
+                ERR_BYTEARRAY_LIMIT_HASH
+            );
+            assert_noop!(
+                Metalog::create_metalog(
+                    Origin::signed(20),
+                    did_new.clone(),
+                    0,
+                    file_hash.clone(),
+                    10,
+                    meta_hash.clone(),
+                    provider,
+                    0,
+                    None
+                ),
+                ERR_PROVIDER_NOT_ACTIVE
+            );
+            let _ = Balances::make_free_balance_be(&30, 10000);
+            assert_ok!(Providers::register(Origin::signed(30), vec![1], 5, 10000));
+            assert_noop!(
+                Metalog::create_metalog(
+                    Origin::signed(20),
+                    did_new.clone(),
+                    0,
+                    file_hash.clone(),
+                    10,
+                    meta_hash.clone(),
+                    provider,
+                    MAX_ROYALTY + 1,
+                    None
+                ),
+                ERR_ROYALTY_INVALID
+            );
             assert_ok!(Metalog::create_metalog(
                 Origin::signed(20),
                 did_new.clone(),
-                0
+                0,
+                file_hash.clone(),
+                10,
+                meta_hash.clone(),
+                provider,
+                50,
+                None
             ));
-            assert_eq!(Metalog::owner_of_did(did_new), Some(20));
+            assert_eq!(Metalog::owner_of_did(&did_new), Some(20));
+            let metadata = Metalog::meta_of_did(&did_new);
+            assert_eq!(metadata.file_hash, file_hash);
+            assert_eq!(metadata.price, 10);
+            assert_eq!(metadata.meta_hash, meta_hash);
+            assert_eq!(metadata.provider, provider);
+            assert_eq!(metadata.creator, 20);
+            assert_eq!(metadata.royalty, 50);
+        });
+    }
+
+    #[test]
+    fn create_metalog_with_publisher_signature_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![4, 5];
+            let payload = Metalog::metalog_payload(&did, 0, &vec![1], &10, &vec![2], &None, 20);
+            assert_noop!(
+                Metalog::create_metalog(
+                    Origin::signed(20),
+                    did.clone(),
+                    0,
+                    vec![1],
+                    10,
+                    vec![2],
+                    None,
+                    20,
+                    Some((30, TestSignature(31, payload.clone())))
+                ),
+                ERR_SIGNATURE_INVALID
+            );
+            assert_noop!(
+                Metalog::create_metalog(
+                    Origin::signed(20),
+                    did.clone(),
+                    0,
+                    vec![1],
+                    11,
+                    vec![2],
+                    None,
+                    20,
+                    Some((30, TestSignature(30, payload.clone())))
+                ),
+                ERR_SIGNATURE_INVALID
+            );
+            assert_ok!(Metalog::create_metalog(
+                Origin::signed(20),
+                did.clone(),
+                0,
+                vec![1],
+                10,
+                vec![2],
+                None,
+                20,
+                Some((30, TestSignature(30, payload)))
+            ));
+            assert_eq!(Metalog::owner_of_did(&did), Some(30));
+            // the publisher is the creator, not the relaying sender
+            assert_eq!(Metalog::meta_of_did(&did).creator, 30);
+            assert_eq!(Metalog::owner_meta_count(&20), 0);
+            assert_eq!(Metalog::owner_meta_count(&30), 1);
+        });
+    }
+
+    #[test]
+    fn pay_sale_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![4, 5];
+            assert_ok!(Metalog::create_metalog(Origin::signed(20), did.clone(), 0, vec![1], 10, vec![2], None, 100, None));
+
+            assert!(Metalog::pay_sale(&30, &20, &did, 1000).is_err());
+            let _ = Balances::make_free_balance_be(&30, 10000);
+
+            // no royalty on sales of the creator
+            assert_ok!(Metalog::pay_sale(&30, &20, &did, 1000));
+            assert_eq!(Balances::free_balance(&20), 1000);
+            assert_eq!(Balances::free_balance(&30), 9000);
+
+            assert_ok!(Metalog::transfer_ownership(Origin::signed(20), 21, did.clone()));
+            assert_ok!(Metalog::pay_sale(&30, &21, &did, 1005));
+            assert_eq!(Balances::free_balance(&20), 1100);
+            assert_eq!(Balances::free_balance(&21), 905);
+
+            // the royalty of small prices rounds down
+            assert_ok!(Metalog::pay_sale(&30, &21, &did, 9));
+            assert_eq!(Balances::free_balance(&20), 1100);
+            assert_eq!(Balances::free_balance(&21), 914);
+            assert_eq!(Balances::free_balance(&30), 7986);
+        });
+    }
+
+    #[test]
+    fn pay_sale_below_existential_deposit_works() {
+        with_externalities(&mut mock::new_test_ext_with(500), || {
+            let did = vec![4, 5];
+            assert_ok!(Metalog::create_metalog(Origin::signed(20), did.clone(), 0, vec![1], 10, vec![2], None, 100, None));
+            assert_ok!(Metalog::transfer_ownership(Origin::signed(20), 21, did.clone()));
+            let _ = Balances::make_free_balance_be(&21, 1000);
+            let _ = Balances::make_free_balance_be(&30, 10000);
+
+            // the royalty can't create the account of the creator and stays with the seller
+            assert_ok!(Metalog::pay_sale(&30, &21, &did, 1000));
+            assert_eq!(Balances::free_balance(&20), 0);
+            assert_eq!(Balances::free_balance(&21), 2000);
+            assert_eq!(Balances::free_balance(&30), 9000);
+
+            assert_ok!(Metalog::pay_sale(&30, &21, &did, 5000));
+            assert_eq!(Balances::free_balance(&20), 500);
+            assert_eq!(Balances::free_balance(&21), 6500);
+            assert_eq!(Balances::free_balance(&30), 4000);
+
+            // the share of a seller without an account needs to create it
+            assert_ok!(Metalog::transfer_ownership(Origin::signed(21), 22, did.clone()));
+            assert_noop!(Metalog::pay_sale(&30, &22, &did, 400), ERR_SELLER_SHARE_LOW);
+            assert_eq!(Balances::free_balance(&30), 4000);
         });
     }
 
@@ -411,12 +1465,198 @@ mod tests {
                 Metalog::transfer_ownership(Origin::signed(1), 2, did_claimed.clone()),
                 ERR_NOT_OWNER
             );
+            assert_noop!(
+                Metalog::transfer_ownership(Origin::signed(0), 0, did_claimed.clone()),
+                ERR_TRANSFER_SELF
+            );
+            let did_kept = vec![7, 7];
+            assert_ok!(Metalog::create_metalog(Origin::signed(0), did_kept.clone(), 0, vec![1], 10, vec![2], None, 0, None));
             assert_ok!(Metalog::transfer_ownership(
                 Origin::signed(0),
                 20,
                 did_claimed.clone()
             ));
-            assert_eq!(Metalog::owner_of_did(did_claimed), Some(20));
+            assert_eq!(Metalog::owner_of_did(&did_claimed), Some(20));
+            // the transferred record moved, the last record took its place
+            assert_eq!(Metalog::owner_meta_count(&0), 1);
+            assert_eq!(Metalog::metadata_of_owner_by_index((0, 0)).did, did_kept);
+            assert_eq!(Metalog::metadata_of_owner_by_index((20, 0)).did, did_claimed);
+            check_owner_index(&[0, 20], &[did_claimed, did_kept]);
+        });
+    }
+
+    #[test]
+    fn change_provider_works() {
+        let did = vec![1, 2, 3];
+        with_externalities(&mut new_test_ext(), || {
+            assert_noop!(
+                Metalog::change_provider(Origin::signed(1), did.clone(), None),
+                ERR_NOT_OWNER
+            );
+            assert_noop!(
+                Metalog::change_provider(Origin::signed(0), did.clone(), Some(0)),
+                ERR_PROVIDER_NOT_ACTIVE
+            );
+            let _ = Balances::make_free_balance_be(&30, 10000);
+            assert_ok!(Providers::register(Origin::signed(30), vec![1], 5, 10000));
+            assert_ok!(Metalog::change_provider(Origin::signed(0), did.clone(), Some(0)));
+            assert_eq!(Metalog::meta_of_did(&did).provider, Some(0));
+
+            // unpinned content doesn't need an active provider
+            assert_ok!(Providers::unregister(Origin::signed(30)));
+            assert_ok!(Metalog::change_provider(Origin::signed(0), did.clone(), None));
+            assert_eq!(Metalog::meta_of_did(&did).provider, None);
+        });
+    }
+
+    /// Invariants of the owned metalog index, every DID is listed once at its index
+    fn check_owner_index(accounts: &[u64], dids: &[Vec<u8>]) {
+        let mut listed = 0;
+        for account in accounts {
+            let count = Metalog::owner_meta_count(account);
+            for index in 0..count {
+                let did = Metalog::did_of_owner_by_index((*account, index));
+                assert_eq!(Metalog::owner_of_did(&did), Some(*account));
+                assert_eq!(<OwnedMetaIndex<Test>>::get(&did), index);
+            }
+            assert!(!<OwnedMetaArray<Test>>::exists((*account, count)));
+            listed += count;
+        }
+        assert_eq!(listed, dids.len() as u64);
+
+        let mut named = 0;
+        for did in dids {
+            let unique_name = Metalog::meta_of_did(did).unique_name;
+            if unique_name != vec![0] {
+                assert_eq!(Metalog::owner_of_un(&unique_name), Metalog::owner_of_did(did));
+                assert_eq!(Metalog::did_of_un(&unique_name), *did);
+                assert_eq!(Metalog::un_by_index(<UnIndex<Test>>::get(&unique_name)), unique_name);
+                named += 1;
+            }
+        }
+        assert_eq!(Metalog::un_count(), named);
+    }
+
+    proptest! {
+        // a failing case is shrunk to a minimal list of operations, which is printed,
+        // and its seed is saved in proptest-regressions, so the following runs replay it first
+        #![proptest_config(ProptestConfig::with_cases(32))]
+        #[test]
+        fn random_index_operations_keep_invariants(
+            operations in prop::collection::vec((0..4usize, any::<prop::sample::Index>(), 0..4u8), 0..200)
+        ) {
+            with_externalities(&mut new_test_ext(), || {
+                let accounts = [0, 1, 2, 3];
+                for account in accounts.iter() {
+                    let _ = Balances::make_free_balance_be(account, 1_000_000_000);
+                }
+                let mut dids = vec![vec![1, 2, 3]];
+                let mut names = 0u16;
+
+                for (step, (account, did, operation)) in operations.into_iter().enumerate() {
+                    let account = accounts[account];
+                    let did = did.get(&dids).clone();
+                    let owner = Metalog::owner_of_did(&did).unwrap();
+                    match operation {
+                        0 => {
+                            let did_new = vec![9, (step >> 8) as u8, step as u8];
+                            assert_ok!(Metalog::create_metalog(Origin::signed(account), did_new.clone(), 0, vec![1], 10, vec![2], None, 0, None));
+                            dids.push(did_new);
+                        }
+                        1 if account == owner => {
+                            assert_noop!(
+                                Metalog::transfer_ownership(Origin::signed(owner), account, did),
+                                ERR_TRANSFER_SELF
+                            );
+                        }
+                        1 => assert_ok!(Metalog::transfer_ownership(Origin::signed(owner), account, did)),
+                        2 => {
+                            names += 1;
+                            let un = vec![110, 110, 110, 110, (names >> 8) as u8, names as u8];
+                            if Metalog::meta_of_did(&did).unique_name == vec![0] {
+                                assert_ok!(Metalog::buy_unique_name(Origin::signed(owner), did, un));
+                            } else {
+                                assert_ok!(Metalog::rename(Origin::signed(owner), did, un));
+                            }
+                        }
+                        _ => {
+                            if Metalog::meta_of_did(&did).unique_name != vec![0] {
+                                assert_ok!(Metalog::release_unique_name(Origin::signed(owner), did));
+                            }
+                        }
+                    }
+                    check_owner_index(&accounts, &dids);
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn legacy_layout_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let did = vec![5, 1];
+            insert_legacy(&did, vec![1, 1, 1, 1, 1]);
+            <DidOwner<Test>>::insert(&did, 5);
+            assert_eq!(Metalog::metalog_by_did(&did), Some(super::Metalog {
+                did: did.clone(),
+                unique_name: vec![1, 1, 1, 1, 1],
+                license_code: 0,
+                file_hash: vec![],
+                price: 0,
+                meta_hash: vec![],
+                provider: None,
+                creator: 5,
+                royalty: 0,
+                time: 7,
+            }));
+            assert_eq!(Metalog::meta_of_did(&vec![5, 2]), super::Metalog::default());
+        });
+    }
+
+    #[test]
+    fn repair_owner_index_works() {
+        with_externalities(&mut new_test_ext(), || {
+            // account 5 stored did_a and did_b with the 1-based index, then transferred did_a to 6,
+            // the earlier transfer moved the record of did_b and keyed the name owner by DID
+            let did_a = vec![5, 1];
+            let did_b = vec![5, 2];
+            let un = vec![1, 1, 1, 1, 1];
+            insert_legacy(&did_a, un.clone());
+            insert_legacy(&did_b, vec![0]);
+            <DidOwner<Test>>::insert(&did_a, 6);
+            <DidOwner<Test>>::insert(&did_b, 5);
+            <UnOwner<Test>>::insert(&un, 5);
+            <UnOwner<Test>>::insert(&did_a, 6);
+            // earlier versions stored full metalogs in the name and owner indices, they are read as their DID
+            runtime_io::set_storage(&map_key(b"Metalog UnMeta", &un), &legacy(&did_a, un.clone()));
+            runtime_io::set_storage(&map_key(b"Metalog OwnedMetaArray", &(5u64, 0u64)), &legacy(&did_a, un.clone()));
+            runtime_io::set_storage(&map_key(b"Metalog OwnedMetaArray", &(6u64, 0u64)), &legacy(&did_b, vec![0]));
+            assert_eq!(Metalog::did_of_un(&un), did_a);
+            <OwnedMetaIndex<Test>>::insert(&did_a, 0);
+            <OwnedMetaIndex<Test>>::insert(&did_b, 2);
+            <OwnedMetaCount<Test>>::insert(&5, 1);
+            <OwnedMetaCount<Test>>::insert(&6, 1);
+
+            assert_noop!(
+                Metalog::repair_owner_index(Origin::signed(9), 0),
+                ERR_INDEX_REPAIRED
+            );
+            assert_ok!(Metalog::repair_owner_index(Origin::signed(9), 5));
+            assert!(Metalog::index_repaired(&5));
+            assert_eq!(Metalog::owner_meta_count(&5), 0);
+            assert_eq!(Metalog::owner_of_un(&did_a), None);
+
+            // account 6 is repaired on its next change
+            let did_c = vec![6, 1];
+            assert_ok!(Metalog::create_metalog(Origin::signed(6), did_c.clone(), 0, vec![1], 10, vec![2], None, 0, None));
+            assert!(Metalog::index_repaired(&6));
+            assert_eq!(Metalog::owner_of_un(&un), Some(6));
+            // the repair stores the metalogs of the account in the current layout
+            let encoded = runtime_io::storage(&map_key(b"Metalog DidMeta", &did_a)).unwrap();
+            let metalog = super::Metalog::<u64, u64, u64>::decode(&mut &encoded[..]).unwrap();
+            assert_eq!(metalog.unique_name, un);
+            assert_eq!(metalog.creator, 6);
+            check_owner_index(&[0, 5, 6], &[vec![1, 2, 3], did_a, did_b, did_c]);
         });
     }
 
@@ -424,7 +1664,7 @@ mod tests {
     fn buy_unique_name_works() {
         let did_claimed = vec![1, 2, 3];
         let did_new = vec![1, 2, 3, 4];
-        let un = vec![1];
+        let un = vec![1, 1, 1, 1, 1];
         let mut un_too_long = un.clone();
         for _i in 1..60 {
             un_too_long.push(2);
@@ -447,6 +1687,10 @@ mod tests {
                 Metalog::buy_unique_name(Origin::signed(0), did_claimed.clone(), un_too_long),
                 ERR_BYTEARRAY_LIMIT_NAME
             );
+            assert_noop!(
+                Metalog::buy_unique_name(Origin::signed(0), did_claimed.clone(), vec![1]),
+                ERR_UN_AUCTION
+            );
             assert_ok!(Metalog::buy_unique_name(
                 Origin::signed(0),
                 did_claimed.clone(),
@@ -470,6 +1714,21 @@ mod tests {
                 Metalog::change_license_code(Origin::signed(1), did_claimed.clone(), 1),
                 ERR_NOT_OWNER
             );
+            assert_noop!(
+                Metalog::change_license_code(Origin::signed(0), did_claimed.clone(), 4),
+                ERR_LICENSE_NOT_REGISTERED
+            );
+            assert_ok!(Metalog::register_license(
+                Origin::ROOT,
+                4,
+                b"CC-BY-4.0".to_vec(),
+                b"Attribution 4.0 International".to_vec(),
+                vec![1, 2],
+                true
+            ));
+            let un = vec![1, 1, 1, 1, 1];
+            let _ = Balances::make_free_balance_be(&0, 500000);
+            assert_ok!(Metalog::buy_unique_name(Origin::signed(0), did_claimed.clone(), un.clone()));
             assert_ok!(Metalog::change_license_code(
                 Origin::signed(0),
                 did_claimed.clone(),
@@ -477,6 +1736,392 @@ mod tests {
             ));
             let metadata = Metalog::meta_of_did(&did_claimed);
             assert_eq!(metadata.license_code, 4);
+            // lookups by name and owner resolve to the same record
+            assert_eq!(Metalog::meta_of_un(&un), metadata);
+            assert_eq!(Metalog::metadata_of_owner_by_index((0, 0)), metadata);
         });
     }
-}
+
+    #[test]
+    fn register_license_works() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_noop!(
+                Metalog::register_license(Origin::signed(0), 4, vec![1], vec![2], vec![3], false),
+                federation::ERR_RANK_INSUFFICIENT
+            );
+            assert_noop!(
+                Metalog::register_license(Origin::ROOT, DELETE_LICENSE, vec![1], vec![2], vec![3], false),
+                ERR_LICENSE_INVALID
+            );
+            let captain = federation::Candidate {
+                current_rank: federation::CAPTAIN_RANK,
+                intended_rank: federation::CAPTAIN_RANK,
+                votes_for: 3001,
+                votes_against: 0,
+                last_change: 0,
+                challenge_start: 0,
+            };
+            let _ = <federation::Module<Test>>::updated_rank_store(0, captain);
+            assert_ok!(Metalog::register_license(Origin::signed(0), 4, vec![1], vec![2], vec![3], false));
+            assert_noop!(
+                Metalog::register_license(Origin::ROOT, 4, vec![1], vec![2], vec![3], false),
+                ERR_LICENSE_ALREADY_REGISTERED
+            );
+            let license = Metalog::license_by_code(4).unwrap();
+            assert_eq!(license.spdx, vec![1]);
+            assert!(!license.commercial_use);
+            assert_noop!(
+                Metalog::create_metalog(Origin::signed(20), vec![7], 5, vec![1], 10, vec![2], None, 0, None),
+                ERR_LICENSE_NOT_REGISTERED
+            );
+            assert_ok!(Metalog::create_metalog(Origin::signed(20), vec![7], 4, vec![1], 10, vec![2], None, 0, None));
+        });
+    }
+
+    #[test]
+    fn delete_request_works() {
+        let did_claimed = vec![1, 2, 3];
+        let un = vec![1, 1, 1, 1, 1];
+        with_externalities(&mut new_test_ext(), || {
+            let _ = Balances::make_free_balance_be(&0, 500000);
+            assert_ok!(Metalog::buy_unique_name(
+                Origin::signed(0),
+                did_claimed.clone(),
+                un.clone()
+            ));
+            System::set_block_number(10);
+            assert_ok!(Metalog::change_license_code(
+                Origin::signed(0),
+                did_claimed.clone(),
+                DELETE_LICENSE
+            ));
+            let metadata = Metalog::meta_of_did(&did_claimed);
+            assert_eq!(metadata.license_code, DELETE_LICENSE);
+            assert_eq!(metadata.unique_name, vec![0]);
+            assert_eq!(Metalog::deletion_of_did(&did_claimed), Some(10));
+            assert_eq!(Metalog::owner_of_un(&un), None);
+            assert_noop!(
+                Metalog::change_license_code(Origin::signed(0), did_claimed.clone(), 4),
+                ERR_DID_DELETED
+            );
+            assert_noop!(
+                Metalog::transfer_ownership(Origin::signed(0), 20, did_claimed),
+                ERR_DID_DELETED
+            );
+        });
+    }
+
+    #[test]
+    fn renew_unique_name_works() {
+        let did_claimed = vec![1, 2, 3];
+        let un = vec![1, 1, 1, 1, 1];
+        with_externalities(&mut new_test_ext(), || {
+            let fee = Metalog::name_fee(un.len());
+            let _ = Balances::make_free_balance_be(&0, 500000);
+            System::set_block_number(1);
+            assert_ok!(Metalog::buy_unique_name(
+                Origin::signed(0),
+                did_claimed.clone(),
+                un.clone()
+            ));
+            let expiry = 1 + NAME_LEASE_PERIOD;
+            assert_eq!(Metalog::expiry_of_un(&un), expiry);
+            assert_noop!(
+                Metalog::renew_unique_name(Origin::signed(1), un.clone()),
+                ERR_UN_NOT_OWNER
+            );
+            assert_ok!(Metalog::renew_unique_name(Origin::signed(0), un.clone()));
+            assert_eq!(Metalog::expiry_of_un(&un), expiry + NAME_LEASE_PERIOD);
+            assert_eq!(Balances::free_balance(&0), 500000 - 2 * fee);
+
+            // the first scheduled release is skipped after the renewal
+            <Metalog as OnFinalize<u64>>::on_finalize(expiry + NAME_GRACE_PERIOD);
+            assert_eq!(Metalog::owner_of_un(&un), Some(0));
+
+            <Metalog as OnFinalize<u64>>::on_finalize(expiry + NAME_LEASE_PERIOD + NAME_GRACE_PERIOD);
+            assert_eq!(Metalog::owner_of_un(&un), None);
+            assert_eq!(Metalog::meta_of_did(&did_claimed).unique_name, vec![0]);
+        });
+    }
+
+    #[test]
+    fn release_unique_name_works() {
+        let did_claimed = vec![1, 2, 3];
+        let un = vec![1, 1, 1, 1, 1];
+        with_externalities(&mut new_test_ext(), || {
+            let fee = Metalog::name_fee(un.len());
+            let _ = Balances::make_free_balance_be(&0, 500000);
+            assert_noop!(
+                Metalog::release_unique_name(Origin::signed(0), did_claimed.clone()),
+                ERR_UN_NOT_SET
+            );
+            assert_ok!(Metalog::buy_unique_name(
+                Origin::signed(0),
+                did_claimed.clone(),
+                un.clone()
+            ));
+            assert_noop!(
+                Metalog::buy_unique_name(Origin::signed(0), did_claimed.clone(), vec![2, 2, 2, 2, 2]),
+                ERR_UN_ALREADY_SET
+            );
+            assert_noop!(
+                Metalog::release_unique_name(Origin::signed(1), did_claimed.clone()),
+                ERR_NOT_OWNER
+            );
+            assert_ok!(Metalog::release_unique_name(Origin::signed(0), did_claimed.clone()));
+            assert_eq!(Balances::free_balance(&0), 500000 - fee + fee / 100 * NAME_REFUND_PERCENT);
+            assert_eq!(Treasury::pot(), fee - fee / 100 * NAME_REFUND_PERCENT);
+            assert_eq!(Metalog::owner_of_un(&un), None);
+            assert_eq!(Metalog::meta_of_did(&did_claimed).unique_name, vec![0]);
+            assert_eq!(Metalog::metadata_of_owner_by_index((0, 0)).unique_name, vec![0]);
+        });
+    }
+
+    #[test]
+    fn release_saturated_fee_works() {
+        let did_claimed = vec![1, 2, 3];
+        let un = vec![1, 1, 1, 1, 1];
+        with_externalities(&mut new_test_ext(), || {
+            let _ = Balances::make_free_balance_be(&0, 500000);
+            assert_ok!(Metalog::buy_unique_name(Origin::signed(0), did_claimed.clone(), un.clone()));
+            // the lease was paid at the saturated fee
+            assert_ok!(Metalog::set_fee_per_used_char(Origin::ROOT, u64::max_value()));
+            <UnFee<Test>>::insert(&un, Metalog::name_fee(un.len()));
+            assert_eq!(Metalog::fee_of_un(&un), u64::max_value());
+
+            // the refund is capped at the pot
+            assert_ok!(Metalog::release_unique_name(Origin::signed(0), did_claimed.clone()));
+            assert_eq!(Treasury::pot(), 0);
+            assert_eq!(Balances::free_balance(&0), 500000);
+        });
+    }
+
+    #[test]
+    fn rename_works() {
+        let did_claimed = vec![1, 2, 3];
+        let un = vec![1, 1, 1, 1, 1];
+        let un_new = vec![2, 2, 2, 2, 2, 2];
+        with_externalities(&mut new_test_ext(), || {
+            let _ = Balances::make_free_balance_be(&0, 500000);
+            assert_noop!(
+                Metalog::rename(Origin::signed(0), did_claimed.clone(), un_new.clone()),
+                ERR_UN_NOT_SET
+            );
+            assert_ok!(Metalog::buy_unique_name(
+                Origin::signed(0),
+                did_claimed.clone(),
+                un.clone()
+            ));
+            assert_noop!(
+                Metalog::rename(Origin::signed(0), did_claimed.clone(), un.clone()),
+                ERR_UN_ALREADY_CLAIMED
+            );
+            assert_ok!(Metalog::rename(Origin::signed(0), did_claimed.clone(), un_new.clone()));
+            assert_eq!(Metalog::owner_of_un(&un), None);
+            assert_eq!(Metalog::owner_of_un(&un_new), Some(0));
+            assert_eq!(Metalog::meta_of_un(&un_new).did, did_claimed.clone());
+            assert_eq!(Metalog::meta_of_did(&did_claimed).unique_name, un_new.clone());
+            assert_eq!(Metalog::metadata_of_owner_by_index((0, 0)).unique_name, un_new);
+        });
+    }
+
+    #[test]
+    fn repoint_unique_name_works() {
+        let did_claimed = vec![1, 2, 3];
+        let did_new = vec![4, 5];
+        let un = vec![1, 1, 1, 1, 1];
+        with_externalities(&mut new_test_ext(), || {
+            let _ = Balances::make_free_balance_be(&0, 500000);
+            assert_ok!(Metalog::buy_unique_name(
+                Origin::signed(0),
+                did_claimed.clone(),
+                un.clone()
+            ));
+            assert_ok!(Metalog::create_metalog(Origin::signed(20), vec![6], 0, vec![1], 10, vec![2], None, 0, None));
+            assert_noop!(
+                Metalog::repoint_unique_name(Origin::signed(0), un.clone(), vec![6]),
+                ERR_NOT_OWNER
+            );
+            assert_ok!(Metalog::create_metalog(Origin::signed(0), did_new.clone(), 0, vec![1], 10, vec![2], None, 0, None));
+            assert_noop!(
+                Metalog::repoint_unique_name(Origin::signed(20), un.clone(), vec![6]),
+                ERR_UN_NOT_OWNER
+            );
+            assert_ok!(Metalog::repoint_unique_name(Origin::signed(0), un.clone(), did_new.clone()));
+            assert_eq!(Metalog::meta_of_did(&did_claimed).unique_name, vec![0]);
+            assert_eq!(Metalog::meta_of_did(&did_new).unique_name, un.clone());
+            assert_eq!(Metalog::meta_of_un(&un).did, did_new);
+        });
+    }
+
+    #[test]
+    fn transfer_unique_name_works() {
+        let did_claimed = vec![1, 2, 3];
+        let did_receiver = vec![4, 5];
+        let un = vec![1, 1, 1, 1, 1];
+        with_externalities(&mut new_test_ext(), || {
+            let _ = Balances::make_free_balance_be(&0, 500000);
+            assert_ok!(Metalog::buy_unique_name(
+                Origin::signed(0),
+                did_claimed.clone(),
+                un.clone()
+            ));
+            assert_noop!(
+                Metalog::transfer_unique_name(Origin::signed(20), un.clone(), 20),
+                ERR_UN_NOT_OWNER
+            );
+            assert_ok!(Metalog::transfer_unique_name(Origin::signed(0), un.clone(), 20));
+            assert_eq!(Metalog::owner_of_un(&un), Some(20));
+            assert_eq!(Metalog::meta_of_did(&did_claimed).unique_name, vec![0]);
+            assert_ok!(Metalog::create_metalog(Origin::signed(20), did_receiver.clone(), 0, vec![1], 10, vec![2], None, 0, None));
+            assert_ok!(Metalog::repoint_unique_name(Origin::signed(20), un.clone(), did_receiver.clone()));
+            assert_eq!(Metalog::meta_of_un(&un).did, did_receiver);
+        });
+    }
+
+    #[test]
+    fn namespaced_names_work() {
+        let did_claimed = vec![1, 2, 3];
+        let name = vec![1, 1];
+        with_externalities(&mut new_test_ext(), || {
+            assert_noop!(
+                Metalog::set_namespaced_name(Origin::signed(20), name.clone(), vec![9]),
+                ERR_DID_NOT_EXIST
+            );
+            assert!(Metalog::set_namespaced_name(Origin::signed(20), name.clone(), did_claimed.clone()).is_err());
+            let _ = Balances::make_free_balance_be(&20, 1000);
+            let _ = Balances::make_free_balance_be(&21, 1000);
+
+            // short names aren't auctioned in namespaces and the same name can be used by every publisher
+            assert_ok!(Metalog::set_namespaced_name(Origin::signed(20), name.clone(), did_claimed.clone()));
+            assert_ok!(Metalog::create_metalog(Origin::signed(21), vec![6], 0, vec![1], 10, vec![2], None, 0, None));
+            assert_ok!(Metalog::set_namespaced_name(Origin::signed(21), name.clone(), vec![6]));
+            assert_eq!(Metalog::did_of_ns_name((20, name.clone())), Some(did_claimed.clone()));
+            assert_eq!(Metalog::did_of_ns_name((21, name.clone())), Some(vec![6]));
+            assert_eq!(Balances::reserved_balance(&20), NAMESPACED_NAME_DEPOSIT);
+
+            // pointing a name to another DID keeps the deposit
+            assert_ok!(Metalog::set_namespaced_name(Origin::signed(20), name.clone(), vec![6]));
+            assert_eq!(Balances::reserved_balance(&20), NAMESPACED_NAME_DEPOSIT);
+
+            assert_noop!(
+                Metalog::remove_namespaced_name(Origin::signed(20), vec![2]),
+                ERR_UN_NOT_IN_NAMESPACE
+            );
+            assert_ok!(Metalog::remove_namespaced_name(Origin::signed(20), name.clone()));
+            assert_eq!(Metalog::did_of_ns_name((20, name.clone())), None);
+            assert_eq!(Balances::reserved_balance(&20), 0);
+            assert_eq!(Balances::free_balance(&20), 1000);
+        });
+    }
+
+    #[test]
+    fn resolve_name_works() {
+        let did_claimed = vec![1, 2, 3];
+        let un = vec![1, 1, 1, 1, 1];
+        with_externalities(&mut new_test_ext(), || {
+            let _ = Balances::make_free_balance_be(&0, 500000);
+            let _ = Balances::make_free_balance_be(&20, 1000);
+            assert_ok!(Metalog::buy_unique_name(Origin::signed(0), did_claimed.clone(), un.clone()));
+            assert_ok!(Metalog::create_metalog(Origin::signed(20), vec![6], 0, vec![1], 10, vec![2], None, 0, None));
+            assert_ok!(Metalog::set_namespaced_name(Origin::signed(20), un.clone(), vec![6]));
+
+            let resolve = |publishers: &[u64]| Metalog::resolve_name(publishers, &un).map(|(publisher, metalog)| (publisher, metalog.did));
+            assert_eq!(resolve(&[]), None);
+            assert_eq!(resolve(&[5]), None);
+            // the global unique name counts as record of its owner
+            assert_eq!(resolve(&[5, 0, 20]), Some((0, did_claimed.clone())));
+            assert_eq!(resolve(&[20, 0]), Some((20, vec![6])));
+
+            // deleted DIDs are skipped
+            assert_ok!(Metalog::change_license_code(Origin::signed(20), vec![6], DELETE_LICENSE));
+            assert_eq!(resolve(&[20, 0]), Some((0, did_claimed.clone())));
+        });
+    }
+
+    #[test]
+    fn resolve_transferred_name_works() {
+        let did = vec![7, 1];
+        let un = vec![1, 1, 1, 1, 1];
+        with_externalities(&mut new_test_ext(), || {
+            let _ = Balances::make_free_balance_be(&7, 500000);
+            assert_ok!(Metalog::create_metalog(Origin::signed(7), did.clone(), 0, vec![1], 10, vec![2], None, 0, None));
+            assert_ok!(Metalog::buy_unique_name(Origin::signed(7), did.clone(), un.clone()));
+
+            // the name follows the DID to the buyer, the creator stays the publisher
+            assert_ok!(Metalog::transfer_ownership(Origin::signed(7), 8, did.clone()));
+            assert_eq!(Metalog::owner_of_un(&un), Some(8));
+            let resolve = |publishers: &[u64]| Metalog::resolve_name(publishers, &un).map(|(publisher, metalog)| (publisher, metalog.did));
+            assert_eq!(resolve(&[8]), None);
+            assert_eq!(resolve(&[8, 7]), Some((7, did.clone())));
+        });
+    }
+
+    #[test]
+    fn runtime_api_queries_work() {
+        with_externalities(&mut new_test_ext(), || {
+            let did_claimed = vec![1, 2, 3];
+            let un = vec![1, 1, 1, 1, 1];
+            for i in 0..3 {
+                assert_ok!(Metalog::create_metalog(Origin::signed(0), vec![8, i], 0, vec![1], 10, vec![2], None, 0, None));
+            }
+            assert_eq!(Metalog::metalog_by_did(&vec![8, 8]), None);
+            assert_eq!(Metalog::metalog_by_did(&did_claimed), Some(Metalog::meta_of_did(&did_claimed)));
+
+            let page = Metalog::metalogs_of_owner(&0, 1, 2);
+            assert_eq!(page.len(), 2);
+            assert_eq!(page[0].did, vec![8, 0]);
+            assert_eq!(page[1].did, vec![8, 1]);
+            assert_eq!(Metalog::metalogs_of_owner(&0, 3, 10).len(), 1);
+            assert!(Metalog::metalogs_of_owner(&0, 5, 10).is_empty());
+
+            assert_eq!(Metalog::name_price(&vec![1]), None);
+            assert_eq!(Metalog::name_price(&un), Some(Metalog::name_fee(un.len())));
+            let _ = Balances::make_free_balance_be(&0, 500000);
+            assert_ok!(Metalog::buy_unique_name(Origin::signed(0), did_claimed.clone(), un.clone()));
+            assert_eq!(Metalog::name_price(&un), None);
+            assert_eq!(Metalog::metalog_by_name(&un).map(|metalog| metalog.did), Some(did_claimed.clone()));
+
+            let un_second = vec![2, 2, 2, 2, 2];
+            assert_ok!(Metalog::buy_unique_name(Origin::signed(0), vec![8, 0], un_second.clone()));
+            assert_eq!(Metalog::unique_names(0, 10), vec![un.clone(), un_second.clone()]);
+            assert_ok!(Metalog::release_unique_name(Origin::signed(0), did_claimed));
+            assert_eq!(Metalog::unique_names(0, 10), vec![un_second]);
+        });
+    }
+
+    #[test]
+    fn set_name_parameters_works() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_noop!(
+                Metalog::set_fee_per_used_char(Origin::signed(0), 200),
+                federation::ERR_RANK_INSUFFICIENT
+            );
+            assert_ok!(Metalog::set_fee_per_used_char(Origin::ROOT, 200));
+            assert_eq!(Metalog::fee_per_used_char(), 200);
+
+            assert_noop!(
+                Metalog::set_name_limit(Origin::ROOT, MAX_NAME_LIMIT + 1),
+                ERR_PARAMETER_INVALID
+            );
+            // the limit can't be below the short name length
+            assert_noop!(Metalog::set_name_limit(Origin::ROOT, 4), ERR_PARAMETER_INVALID);
+            assert_ok!(Metalog::set_name_limit(Origin::ROOT, 20));
+            assert_eq!(Metalog::name_fee(10), 200 * 11 * 11);
+            assert_eq!(Metalog::name_fee(30), 200);
+
+            assert_noop!(
+                Metalog::set_short_name_length(Origin::ROOT, 21),
+                ERR_PARAMETER_INVALID
+            );
+            assert_ok!(Metalog::set_short_name_length(Origin::ROOT, 3));
+            assert!(!Metalog::is_short_name(&vec![1, 1, 1]));
+            assert_ok!(Metalog::set_name_limit(Origin::ROOT, 3));
+            assert_noop!(Metalog::set_name_limit(Origin::ROOT, 2), ERR_PARAMETER_INVALID);
+
+            // the fee saturates instead of overflowing
+            assert_ok!(Metalog::set_fee_per_used_char(Origin::ROOT, u64::max_value()));
+            assert_eq!(Metalog::name_fee(0), u64::max_value());
+        });
+    }
+}
\ No newline at end of file
@@ -0,0 +1,132 @@
+//! Test runtime shared by the tests of the Starlog modules.
+
+use parity_codec::{Decode, Encode};
+use primitives::{Blake2Hasher, H256};
+use rstd::vec::Vec;
+use runtime_primitives::{
+    testing::{Digest, DigestItem, Header},
+    traits::{BlakeTwo256, IdentityLookup, Lazy, Verify},
+    BuildStorage,
+};
+use support::{assert_ok, dispatch::Dispatchable, impl_outer_origin};
+
+use crate::{
+    availability, deals, federation, follow_list, licensing, marketplace, metalog, name_auction, providers, treasury,
+};
+
+impl_outer_origin! {
+    pub enum Origin for Test {}
+}
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct Test;
+
+impl system::Trait for Test {
+    type Origin = Origin;
+    type Index = u64;
+    type BlockNumber = u64;
+    type Hash = H256;
+    type Hashing = BlakeTwo256;
+    type Digest = Digest;
+    type AccountId = u64;
+    type Lookup = IdentityLookup<Self::AccountId>;
+    type Header = Header;
+    type Event = ();
+    type Log = DigestItem;
+}
+
+impl balances::Trait for Test {
+    type Balance = u64;
+    type OnFreeBalanceZero = ();
+    type OnNewAccount = ();
+    type Event = ();
+    type TransactionPayment = ();
+    type DustRemoval = ();
+    type TransferPayment = ();
+}
+
+impl timestamp::Trait for Test {
+    type Moment = u64;
+    type OnTimestampSet = ();
+}
+
+impl federation::Trait for Test {
+    type Event = ();
+}
+
+/// Signature of a publisher: the signer and the signed message
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct TestSignature(pub u64, pub Vec<u8>);
+
+impl Verify for TestSignature {
+    type Signer = u64;
+    fn verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &u64) -> bool {
+        *signer == self.0 && msg.get() == &self.1[..]
+    }
+}
+
+impl treasury::Trait for Test {
+    type Event = ();
+}
+
+impl providers::Trait for Test {
+    type Event = ();
+}
+
+impl metalog::Trait for Test {
+    type Event = ();
+    type PublisherSignature = TestSignature;
+    type OnDeleteRequest = deals::Module<Test>;
+}
+
+impl name_auction::Trait for Test {
+    type Event = ();
+}
+
+impl follow_list::Trait for Test {
+    type Event = ();
+}
+
+impl availability::Trait for Test {
+    type Event = ();
+}
+
+impl deals::Trait for Test {
+    type Event = ();
+}
+
+impl marketplace::Trait for Test {
+    type Event = ();
+}
+
+impl licensing::Trait for Test {
+    type Event = ();
+}
+
+pub fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+    new_test_ext_with(0)
+}
+
+/// Test externalities with the given existential deposit of the balances module
+pub fn new_test_ext_with(existential_deposit: u64) -> runtime_io::TestExternalities<Blake2Hasher> {
+    let mut t = system::GenesisConfig::<Test>::default()
+        .build_storage()
+        .unwrap()
+        .0;
+    t.extend(
+        balances::GenesisConfig::<Test> {
+            existential_deposit,
+            ..Default::default()
+        }
+        .build_storage()
+        .unwrap()
+        .0,
+    );
+    t.into()
+}
+
+/// Stores a metalog for the DID without a name, provider or royalty
+pub fn create(owner: u64, did: Vec<u8>) {
+    let call = metalog::Call::<Test>::create_metalog(did, 0, vec![1], 0, vec![2], None, 0, None);
+    assert_ok!(call.dispatch(Origin::signed(owner)));
+}
@@ -0,0 +1,404 @@
+//! # Name Auction Module
+//!
+//! The Name Auction module sells short unique names through a first-price sealed-bid auction.
+//! Bidders commit the hash of their bid together with a deposit, which hides the actual bid.
+//! The deposit needs to cover the reserve price, the regular fee of the name.
+//! After the reveal phase, the highest revealed bid wins and pays its bid, all other revealed deposits are refunded.
+//! The winning bid needs to reach the regular fee of the name, otherwise the auction closes without a winner and all revealed deposits are refunded.
+//! Deposits of unrevealed bids are forfeited. Paid bids and forfeited deposits go to the treasury.
+
+use parity_codec::{Decode, Encode};
+use rstd::vec::Vec;
+use runtime_primitives::traits::{As, Hash};
+use support::{
+    decl_event, decl_module, decl_storage,
+    dispatch::Result,
+    ensure,
+    traits::ReservableCurrency,
+    StorageMap,
+};
+use system::ensure_signed;
+
+use crate::{metalog, treasury};
+
+const ERR_NOT_SHORT_NAME: &str = "Only short unique names are auctioned";
+const ERR_NAME_INVALID: &str = "This unique name is invalid";
+const ERR_NAME_CLAIMED: &str = "This unique name has already been claimed";
+
+const ERR_AUCTION_NOT_EXIST: &str = "There is no auction for this unique name";
+const ERR_AUCTION_NOT_ENDED: &str = "The reveal phase of this auction hasn't ended yet";
+
+const ERR_COMMIT_CLOSED: &str = "The commit phase of this auction has ended";
+const ERR_NOT_REVEAL_PHASE: &str = "Bids can only be revealed during the reveal phase";
+
+const ERR_BID_EXIST: &str = "You already placed a bid in this auction";
+const ERR_BID_NOT_EXIST: &str = "You haven't placed a bid in this auction";
+const ERR_BID_REVEALED: &str = "This bid has already been revealed";
+const ERR_BID_MISMATCH: &str = "The bid and salt don't match the commitment";
+const ERR_BID_ABOVE_DEPOSIT: &str = "The bid can't be higher than the deposit";
+const ERR_BID_ZERO_DEPOSIT: &str = "The deposit needs to be above zero";
+const ERR_DEPOSIT_BELOW_RESERVE: &str = "The deposit needs to cover the reserve price of the name";
+const ERR_BIDDERS_LIMIT: &str = "This auction has reached the maximum number of bidders";
+
+/// Commit phase of one week with 6 seconds blocktime
+const COMMIT_PERIOD: u64 = 100800;
+/// Reveal phase of three days with 6 seconds blocktime
+const REVEAL_PERIOD: u64 = 43200;
+/// Limits the work done while settling an auction
+const MAX_BIDDERS: usize = 50;
+
+/// The module's configuration trait.
+pub trait Trait: metalog::Trait {
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+/// Auction of a single short unique name
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Auction<AccountId, Balance, BlockNumber> {
+    pub commit_end: BlockNumber,
+    pub reveal_end: BlockNumber,
+    pub highest_bidder: Option<AccountId>,
+    pub highest_bid: Balance,
+}
+
+/// Sealed bid, commitment = hash(bidder, unique name, bid, salt)
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Bid<Hash, Balance> {
+    pub commitment: Hash,
+    pub deposit: Balance,
+    pub revealed: bool,
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as NameAuction {
+        /// Running auctions by unique name
+        Auctions get(auction_of): map Vec<u8> => Option<Auction<T::AccountId, T::Balance, T::BlockNumber>>;
+
+        /// Bids by (unique name, bidder)
+        Bids get(bid_of): map (Vec<u8>, T::AccountId) => Option<Bid<T::Hash, T::Balance>>;
+
+        /// Bidders of an auction
+        Bidders get(bidders_of): map Vec<u8> => Vec<T::AccountId>;
+    }
+}
+
+decl_module! {
+    /// The module declaration.
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        fn deposit_event<T>() = default;
+
+        /// Commit a sealed bid, the first bid starts the auction
+        /// The deposit is reserved and needs to cover the bid
+        fn commit_bid(origin, unique_name: Vec<u8>, commitment: T::Hash, deposit: T::Balance) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            // the empty name and the default name of metalogs without a unique name can't be claimed
+            ensure!(!unique_name.is_empty() && unique_name[..] != [0], ERR_NAME_INVALID);
+            ensure!(<metalog::Module<T>>::is_short_name(&unique_name), ERR_NOT_SHORT_NAME);
+            ensure!(<metalog::Module<T>>::owner_of_un(&unique_name).is_none(), ERR_NAME_CLAIMED);
+            ensure!(deposit > T::Balance::sa(0), ERR_BID_ZERO_DEPOSIT);
+            // bids which can't win don't take one of the limited bidder slots
+            ensure!(deposit >= <metalog::Module<T>>::name_fee(unique_name.len()), ERR_DEPOSIT_BELOW_RESERVE);
+
+            let block_number = <system::Module<T>>::block_number();
+            let auction = match Self::auction_of(&unique_name) {
+                Some(auction) => auction,
+                None => {
+                    let commit_end = block_number + T::BlockNumber::sa(COMMIT_PERIOD);
+                    let auction = Auction {
+                        commit_end,
+                        reveal_end: commit_end + T::BlockNumber::sa(REVEAL_PERIOD),
+                        highest_bidder: None,
+                        highest_bid: T::Balance::sa(0),
+                    };
+                    Self::deposit_event(RawEvent::AuctionStarted(unique_name.clone(), auction.commit_end, auction.reveal_end));
+                    auction
+                }
+            };
+            ensure!(block_number < auction.commit_end, ERR_COMMIT_CLOSED);
+            ensure!(!<Bids<T>>::exists((unique_name.clone(), sender.clone())), ERR_BID_EXIST);
+
+            let mut bidders = Self::bidders_of(&unique_name);
+            ensure!(bidders.len() < MAX_BIDDERS, ERR_BIDDERS_LIMIT);
+
+            <balances::Module<T> as ReservableCurrency<_>>::reserve(&sender, deposit)?;
+
+            let bid = Bid {
+                commitment,
+                deposit,
+                revealed: false,
+            };
+            bidders.push(sender.clone());
+            <Bids<T>>::insert((unique_name.clone(), sender.clone()), bid);
+            <Bidders<T>>::insert(&unique_name, bidders);
+            <Auctions<T>>::insert(&unique_name, auction);
+
+            Self::deposit_event(RawEvent::BidCommitted(sender, unique_name, deposit));
+            Ok(())
+        }
+
+        /// Reveal a sealed bid during the reveal phase
+        fn reveal_bid(origin, unique_name: Vec<u8>, bid: T::Balance, salt: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let mut auction = Self::auction_of(&unique_name).ok_or(ERR_AUCTION_NOT_EXIST)?;
+            let block_number = <system::Module<T>>::block_number();
+            ensure!(block_number >= auction.commit_end && block_number < auction.reveal_end, ERR_NOT_REVEAL_PHASE);
+
+            let key = (unique_name.clone(), sender.clone());
+            let mut sealed_bid = Self::bid_of(&key).ok_or(ERR_BID_NOT_EXIST)?;
+            ensure!(!sealed_bid.revealed, ERR_BID_REVEALED);
+            let commitment = T::Hashing::hash_of(&(sender.clone(), unique_name.clone(), bid, salt));
+            ensure!(commitment == sealed_bid.commitment, ERR_BID_MISMATCH);
+            ensure!(bid <= sealed_bid.deposit, ERR_BID_ABOVE_DEPOSIT);
+
+            sealed_bid.revealed = true;
+            <Bids<T>>::insert(&key, sealed_bid);
+
+            // on equal bids, the first revealed bid wins
+            if auction.highest_bidder.is_none() || bid > auction.highest_bid {
+                auction.highest_bidder = Some(sender.clone());
+                auction.highest_bid = bid;
+                <Auctions<T>>::insert(&unique_name, auction);
+            }
+
+            Self::deposit_event(RawEvent::BidRevealed(sender, unique_name, bid));
+            Ok(())
+        }
+
+        /// Settle an auction after the reveal phase, can be called by anyone
+        /// Without a claimable name or a bid at the reserve price, all revealed deposits are refunded
+        fn settle_auction(origin, unique_name: Vec<u8>) -> Result {
+            let _ = ensure_signed(origin)?;
+
+            let mut auction = Self::auction_of(&unique_name).ok_or(ERR_AUCTION_NOT_EXIST)?;
+            ensure!(<system::Module<T>>::block_number() >= auction.reveal_end, ERR_AUCTION_NOT_ENDED);
+
+            // decided before any balance is touched, the highest bidder is only charged if the name can be claimed
+            let reserve_price = <metalog::Module<T>>::name_fee(unique_name.len());
+            if <metalog::Module<T>>::owner_of_un(&unique_name).is_some() || auction.highest_bid < reserve_price {
+                auction.highest_bidder = None;
+            }
+
+            for bidder in <Bidders<T>>::take(&unique_name) {
+                if let Some(bid) = <Bids<T>>::take((unique_name.clone(), bidder.clone())) {
+                    Self::_settle_bid(&bidder, &bid, &auction);
+                }
+            }
+            <Auctions<T>>::remove(&unique_name);
+
+            match auction.highest_bidder {
+                Some(winner) => {
+                    // can't fail after the name has been checked
+                    <metalog::Module<T>>::claim_auctioned_name(winner.clone(), unique_name.clone(), auction.highest_bid)?;
+                    Self::deposit_event(RawEvent::AuctionSettled(unique_name, winner, auction.highest_bid));
+                }
+                None => Self::deposit_event(RawEvent::AuctionClosed(unique_name)),
+            }
+            Ok(())
+        }
+    }
+}
+
+decl_event!(
+    pub enum Event<T> where
+        <T as system::Trait>::AccountId,
+        <T as system::Trait>::BlockNumber,
+        <T as balances::Trait>::Balance
+    {
+        /// unique name, end of commit phase, end of reveal phase
+        AuctionStarted(Vec<u8>, BlockNumber, BlockNumber),
+        /// bidder, unique name, deposit
+        BidCommitted(AccountId, Vec<u8>, Balance),
+        /// bidder, unique name, bid
+        BidRevealed(AccountId, Vec<u8>, Balance),
+        /// unique name, winner, paid bid
+        AuctionSettled(Vec<u8>, AccountId, Balance),
+        /// unique name, auction ended without a winning bid
+        AuctionClosed(Vec<u8>),
+    }
+);
+
+impl<T: Trait> Module<T> {
+    /// Charges the winner, refunds revealed losing bids and forfeits unrevealed deposits
+    fn _settle_bid(
+        bidder: &T::AccountId,
+        bid: &Bid<T::Hash, T::Balance>,
+        auction: &Auction<T::AccountId, T::Balance, T::BlockNumber>,
+    ) {
+        if auction.highest_bidder.as_ref() == Some(bidder) {
+            Self::_slash_to_treasury(bidder, auction.highest_bid);
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(bidder, bid.deposit - auction.highest_bid);
+        } else if bid.revealed {
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(bidder, bid.deposit);
+        } else {
+            Self::_slash_to_treasury(bidder, bid.deposit);
+        }
+    }
+
+    /// Slashes reserved funds into the treasury pot
+    fn _slash_to_treasury(who: &T::AccountId, amount: T::Balance) {
+        <treasury::Module<T>>::slash_reserved(who, amount);
+    }
+}
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock::{new_test_ext, Origin, Test};
+    use primitives::H256;
+    use runtime_io::with_externalities;
+    use runtime_primitives::traits::BlakeTwo256;
+    use support::{assert_noop, assert_ok};
+
+    type Balances = balances::Module<Test>;
+    type System = system::Module<Test>;
+    type Treasury = treasury::Module<Test>;
+    type Metalog = metalog::Module<Test>;
+    type NameAuction = Module<Test>;
+
+    fn commitment(bidder: u64, unique_name: &Vec<u8>, bid: u64, salt: &Vec<u8>) -> H256 {
+        BlakeTwo256::hash_of(&(bidder, unique_name.clone(), bid, salt.clone()))
+    }
+
+    #[test]
+    fn commit_bid_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let un = vec![1, 2];
+            let salt = vec![7];
+            // reserve price of 49 * 49 with a name limit of 50
+            assert_ok!(Metalog::set_fee_per_used_char(Origin::ROOT, 1));
+            let _ = Balances::make_free_balance_be(&1, 10000);
+            assert_noop!(
+                NameAuction::commit_bid(Origin::signed(1), un.clone(), commitment(1, &un, 10, &salt), 2400),
+                ERR_DEPOSIT_BELOW_RESERVE
+            );
+            assert_ok!(Metalog::set_fee_per_used_char(Origin::ROOT, 0));
+            assert_noop!(
+                NameAuction::commit_bid(Origin::signed(1), vec![1, 2, 3, 4, 5], commitment(1, &un, 10, &salt), 20),
+                ERR_NOT_SHORT_NAME
+            );
+            assert_noop!(
+                NameAuction::commit_bid(Origin::signed(1), vec![], commitment(1, &vec![], 10, &salt), 20),
+                ERR_NAME_INVALID
+            );
+            assert_noop!(
+                NameAuction::commit_bid(Origin::signed(1), vec![0], commitment(1, &vec![0], 10, &salt), 20),
+                ERR_NAME_INVALID
+            );
+            assert_ok!(NameAuction::commit_bid(Origin::signed(1), un.clone(), commitment(1, &un, 10, &salt), 20));
+            assert_noop!(
+                NameAuction::commit_bid(Origin::signed(1), un.clone(), commitment(1, &un, 10, &salt), 20),
+                ERR_BID_EXIST
+            );
+            assert_eq!(Balances::reserved_balance(&1), 20);
+            assert_eq!(NameAuction::auction_of(&un).unwrap().commit_end, COMMIT_PERIOD);
+
+            System::set_block_number(COMMIT_PERIOD);
+            let _ = Balances::make_free_balance_be(&2, 1000);
+            assert_noop!(
+                NameAuction::commit_bid(Origin::signed(2), un.clone(), commitment(2, &un, 10, &salt), 20),
+                ERR_COMMIT_CLOSED
+            );
+        });
+    }
+
+    #[test]
+    fn auction_settlement_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let un = vec![1, 2];
+            let salt = vec![7];
+            // without a reserve price
+            assert_ok!(Metalog::set_fee_per_used_char(Origin::ROOT, 0));
+            for bidder in 1..4 {
+                let _ = Balances::make_free_balance_be(&bidder, 1000);
+            }
+            assert_ok!(NameAuction::commit_bid(Origin::signed(1), un.clone(), commitment(1, &un, 100, &salt), 200));
+            assert_ok!(NameAuction::commit_bid(Origin::signed(2), un.clone(), commitment(2, &un, 150, &salt), 300));
+            assert_ok!(NameAuction::commit_bid(Origin::signed(3), un.clone(), commitment(3, &un, 500, &salt), 500));
+            assert_noop!(
+                NameAuction::reveal_bid(Origin::signed(1), un.clone(), 100, salt.clone()),
+                ERR_NOT_REVEAL_PHASE
+            );
+
+            System::set_block_number(COMMIT_PERIOD);
+            assert_noop!(
+                NameAuction::reveal_bid(Origin::signed(1), un.clone(), 101, salt.clone()),
+                ERR_BID_MISMATCH
+            );
+            assert_ok!(NameAuction::reveal_bid(Origin::signed(1), un.clone(), 100, salt.clone()));
+            assert_ok!(NameAuction::reveal_bid(Origin::signed(2), un.clone(), 150, salt.clone()));
+            assert_noop!(
+                NameAuction::settle_auction(Origin::signed(4), un.clone()),
+                ERR_AUCTION_NOT_ENDED
+            );
+
+            // bidder 3 never reveals
+            System::set_block_number(COMMIT_PERIOD + REVEAL_PERIOD);
+            assert_ok!(NameAuction::settle_auction(Origin::signed(4), un.clone()));
+            assert_eq!(Metalog::owner_of_un(&un), Some(2));
+            assert_eq!(Balances::free_balance(&1), 1000);
+            assert_eq!(Balances::free_balance(&2), 850);
+            assert_eq!(Balances::free_balance(&3), 500);
+            assert_eq!(Balances::reserved_balance(&2), 0);
+            assert_eq!(Balances::reserved_balance(&3), 0);
+            assert_eq!(Treasury::pot(), 650);
+            assert_eq!(NameAuction::auction_of(&un), None);
+            assert!(NameAuction::bidders_of(&un).is_empty());
+        });
+    }
+
+    #[test]
+    fn reserve_price_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let un = vec![1, 2];
+            let salt = vec![7];
+            // reserve price of 49 * 49 with a name limit of 50
+            assert_ok!(Metalog::set_fee_per_used_char(Origin::ROOT, 1));
+            assert_eq!(Metalog::name_fee(un.len()), 2401);
+            for bidder in 1..3 {
+                let _ = Balances::make_free_balance_be(&bidder, 5000);
+            }
+            assert_ok!(NameAuction::commit_bid(Origin::signed(1), un.clone(), commitment(1, &un, 2400, &salt), 3000));
+            assert_ok!(NameAuction::commit_bid(Origin::signed(2), un.clone(), commitment(2, &un, 2401, &salt), 3000));
+
+            // bidder 2 never reveals, the bid of bidder 1 is below the reserve price
+            System::set_block_number(COMMIT_PERIOD);
+            assert_ok!(NameAuction::reveal_bid(Origin::signed(1), un.clone(), 2400, salt.clone()));
+            System::set_block_number(COMMIT_PERIOD + REVEAL_PERIOD);
+            assert_ok!(NameAuction::settle_auction(Origin::signed(3), un.clone()));
+            assert_eq!(Metalog::owner_of_un(&un), None);
+            assert_eq!(Balances::free_balance(&1), 5000);
+            assert_eq!(Balances::reserved_balance(&1), 0);
+            assert_eq!(Balances::free_balance(&2), 2000);
+            assert_eq!(Balances::reserved_balance(&2), 0);
+            assert_eq!(NameAuction::auction_of(&un), None);
+        });
+    }
+
+    #[test]
+    fn settle_claimed_name_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let un = vec![1, 2];
+            let salt = vec![7];
+            assert_ok!(Metalog::set_fee_per_used_char(Origin::ROOT, 0));
+            let _ = Balances::make_free_balance_be(&1, 1000);
+            assert_ok!(NameAuction::commit_bid(Origin::signed(1), un.clone(), commitment(1, &un, 100, &salt), 200));
+            System::set_block_number(COMMIT_PERIOD);
+            assert_ok!(NameAuction::reveal_bid(Origin::signed(1), un.clone(), 100, salt.clone()));
+
+            // the name has been claimed in the meantime, the highest bidder isn't charged
+            assert_ok!(Metalog::claim_auctioned_name(2, un.clone(), 0));
+            System::set_block_number(COMMIT_PERIOD + REVEAL_PERIOD);
+            assert_ok!(NameAuction::settle_auction(Origin::signed(3), un.clone()));
+            assert_eq!(Metalog::owner_of_un(&un), Some(2));
+            assert_eq!(Balances::free_balance(&1), 1000);
+            assert_eq!(Balances::reserved_balance(&1), 0);
+            assert_eq!(Treasury::pot(), 0);
+        });
+    }
+}
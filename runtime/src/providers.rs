@@ -0,0 +1,321 @@
+//! # Providers Module
+//!
+//! The Providers module is the registry of IPFS pinning providers, which metalogs reference by ID.
+//! Providers register with a multiaddr, a price per GiB-month and a bonded stake, which is reserved from their balance.
+//! A provider failing audits can be slashed by root or higher federation ranks, slashed stake goes to the treasury.
+//! Unregistered providers can withdraw their stake after an unbonding period, during which they can still be slashed.
+
+use parity_codec::{Decode, Encode};
+use rstd::vec::Vec;
+use runtime_primitives::traits::{As, CheckedAdd};
+use support::{
+    decl_event, decl_module, decl_storage,
+    dispatch::Result,
+    ensure,
+    traits::ReservableCurrency,
+    StorageMap, StorageValue,
+};
+use system::ensure_signed;
+
+use crate::{federation, treasury};
+
+const ERR_PROVIDER_NOT_EXIST: &str = "This pinning provider does not exist";
+const ERR_PROVIDER_REGISTERED: &str = "This account has already registered a pinning provider";
+const ERR_PROVIDER_UNBONDING: &str = "This pinning provider has been unregistered";
+const ERR_PROVIDER_NOT_UNBONDING: &str = "Unregister the pinning provider before withdrawing the stake";
+const ERR_UNBONDING_NOT_ENDED: &str = "The stake is still bonded";
+
+const ERR_STAKE_LOW: &str = "The stake is below the minimum stake";
+
+const ERR_OVERFLOW: &str = "Overflow adding a new pinning provider";
+const ERR_STAKE_OVERFLOW: &str = "Overflow adding to the stake";
+
+const ERR_BYTEARRAY_LIMIT_MULTIADDR: &str = "Multiaddr bytearray is too large";
+
+const BYTEARRAY_LIMIT_MULTIADDR: usize = 200;
+
+/// Minimum federation rank to slash providers
+const SLASH_RANK: u16 = federation::CAPTAIN_RANK;
+
+/// Minimum stake of an active provider
+const MIN_STAKE: u64 = 10000;
+/// The stake stays bonded for four weeks after unregistering with 6 seconds blocktime
+const UNBONDING_PERIOD: u64 = 403200;
+
+/// The module's configuration trait.
+pub trait Trait: balances::Trait + federation::Trait + treasury::Trait {
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+/// Registered pinning provider
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Provider<AccountId, Balance, BlockNumber> {
+    /// Account, which bonded the stake
+    pub account: AccountId,
+    /// Multiaddr of the pinning service
+    pub multiaddr: Vec<u8>,
+    /// Price per GiB-month
+    pub price: Balance,
+    /// Bonded stake
+    pub stake: Balance,
+    /// Block at which the stake can be withdrawn, set once the provider unregistered
+    pub unbonding_end: Option<BlockNumber>,
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Providers {
+        /// Pinning providers by ID
+        Providers get(provider): map u64 => Option<Provider<T::AccountId, T::Balance, T::BlockNumber>>;
+        /// Number of registered providers, the ID of the next provider
+        ProviderCount get(provider_count): u64;
+
+        /// Provider ID of an account
+        ProviderOf get(provider_of): map T::AccountId => Option<u64>;
+    }
+}
+
+decl_module! {
+    /// The module declaration.
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        fn deposit_event<T>() = default;
+
+        /// Register a pinning provider, the stake is reserved
+        pub fn register(origin, multiaddr: Vec<u8>, price: T::Balance, stake: T::Balance) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(Self::provider_of(&sender).is_none(), ERR_PROVIDER_REGISTERED);
+            ensure!(multiaddr.len() <= BYTEARRAY_LIMIT_MULTIADDR, ERR_BYTEARRAY_LIMIT_MULTIADDR);
+            ensure!(stake >= T::Balance::sa(MIN_STAKE), ERR_STAKE_LOW);
+            let id = Self::provider_count();
+            let updated_count = id.checked_add(1).ok_or(ERR_OVERFLOW)?;
+
+            <balances::Module<T> as ReservableCurrency<_>>::reserve(&sender, stake)?;
+
+            let provider = Provider {
+                account: sender.clone(),
+                multiaddr,
+                price,
+                stake,
+                unbonding_end: None,
+            };
+            <Providers<T>>::insert(id, provider);
+            <ProviderCount<T>>::put(updated_count);
+            <ProviderOf<T>>::insert(&sender, id);
+
+            Self::deposit_event(RawEvent::Registered(id, sender, stake));
+            Ok(())
+        }
+
+        /// Update the multiaddr and price of the sender's provider
+        pub fn update(origin, multiaddr: Vec<u8>, price: T::Balance) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(multiaddr.len() <= BYTEARRAY_LIMIT_MULTIADDR, ERR_BYTEARRAY_LIMIT_MULTIADDR);
+            let (id, mut provider) = Self::_provider_of_account(&sender)?;
+            ensure!(provider.unbonding_end.is_none(), ERR_PROVIDER_UNBONDING);
+
+            provider.multiaddr = multiaddr;
+            provider.price = price;
+            <Providers<T>>::insert(id, provider);
+
+            Self::deposit_event(RawEvent::Updated(id, price));
+            Ok(())
+        }
+
+        /// Bond additional stake, e.g. after a slash
+        pub fn bond_extra(origin, amount: T::Balance) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let (id, mut provider) = Self::_provider_of_account(&sender)?;
+            ensure!(provider.unbonding_end.is_none(), ERR_PROVIDER_UNBONDING);
+            let stake = provider.stake.checked_add(&amount).ok_or(ERR_STAKE_OVERFLOW)?;
+
+            <balances::Module<T> as ReservableCurrency<_>>::reserve(&sender, amount)?;
+            provider.stake = stake;
+            <Providers<T>>::insert(id, provider);
+
+            Self::deposit_event(RawEvent::Bonded(id, amount));
+            Ok(())
+        }
+
+        /// Unregister the sender's provider, the stake can be withdrawn after the unbonding period
+        pub fn unregister(origin) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let (id, mut provider) = Self::_provider_of_account(&sender)?;
+            ensure!(provider.unbonding_end.is_none(), ERR_PROVIDER_UNBONDING);
+
+            let unbonding_end = <system::Module<T>>::block_number() + T::BlockNumber::sa(UNBONDING_PERIOD);
+            provider.unbonding_end = Some(unbonding_end);
+            <Providers<T>>::insert(id, provider);
+
+            Self::deposit_event(RawEvent::Unregistered(id, unbonding_end));
+            Ok(())
+        }
+
+        /// Withdraw the stake after the unbonding period and remove the provider
+        pub fn withdraw(origin) -> Result {
+            let sender = ensure_signed(origin)?;
+
+            let (id, provider) = Self::_provider_of_account(&sender)?;
+            let unbonding_end = provider.unbonding_end.ok_or(ERR_PROVIDER_NOT_UNBONDING)?;
+            ensure!(<system::Module<T>>::block_number() >= unbonding_end, ERR_UNBONDING_NOT_ENDED);
+
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&sender, provider.stake);
+            <Providers<T>>::remove(id);
+            <ProviderOf<T>>::remove(&sender);
+
+            Self::deposit_event(RawEvent::Withdrawn(id, provider.stake));
+            Ok(())
+        }
+
+        /// Slash the stake of a provider, only root or higher federation ranks
+        pub fn slash(origin, id: u64, amount: T::Balance) -> Result {
+            <federation::Module<T>>::ensure_root_or_rank(origin, SLASH_RANK)?;
+
+            let mut provider = Self::provider(id).ok_or(ERR_PROVIDER_NOT_EXIST)?;
+            let amount = if amount < provider.stake { amount } else { provider.stake };
+            let slashed = <treasury::Module<T>>::slash_reserved(&provider.account, amount);
+
+            provider.stake -= slashed;
+            <Providers<T>>::insert(id, provider);
+
+            Self::deposit_event(RawEvent::Slashed(id, slashed));
+            Ok(())
+        }
+    }
+}
+
+decl_event!(
+    pub enum Event<T> where
+        <T as system::Trait>::AccountId,
+        <T as system::Trait>::BlockNumber,
+        <T as balances::Trait>::Balance
+    {
+        /// provider id, account, stake
+        Registered(u64, AccountId, Balance),
+        /// provider id, new price per GiB-month
+        Updated(u64, Balance),
+        /// provider id, additional stake
+        Bonded(u64, Balance),
+        /// provider id, end of the unbonding period
+        Unregistered(u64, BlockNumber),
+        /// provider id, returned stake
+        Withdrawn(u64, Balance),
+        /// provider id, slashed stake
+        Slashed(u64, Balance),
+    }
+);
+
+impl<T: Trait> Module<T> {
+    /// Whether metalogs can reference the provider, it needs to be registered with the minimum stake
+    pub fn is_active(id: u64) -> bool {
+        match Self::provider(id) {
+            Some(provider) => provider.unbonding_end.is_none() && provider.stake >= T::Balance::sa(MIN_STAKE),
+            None => false,
+        }
+    }
+
+    fn _provider_of_account(
+        account: &T::AccountId,
+    ) -> rstd::result::Result<(u64, Provider<T::AccountId, T::Balance, T::BlockNumber>), &'static str> {
+        let id = Self::provider_of(account).ok_or(ERR_PROVIDER_NOT_EXIST)?;
+        let provider = Self::provider(id).ok_or(ERR_PROVIDER_NOT_EXIST)?;
+        Ok((id, provider))
+    }
+}
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock::{new_test_ext, Origin, Test};
+    use runtime_io::with_externalities;
+    use support::{assert_noop, assert_ok};
+
+    type Balances = balances::Module<Test>;
+    type System = system::Module<Test>;
+    type Treasury = treasury::Module<Test>;
+    type Providers = Module<Test>;
+
+    #[test]
+    fn register_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let multiaddr = b"/dns4/pin.example.com/tcp/4001".to_vec();
+            assert_noop!(
+                Providers::register(Origin::signed(1), multiaddr.clone(), 5, MIN_STAKE - 1),
+                ERR_STAKE_LOW
+            );
+            assert!(Providers::register(Origin::signed(1), multiaddr.clone(), 5, MIN_STAKE).is_err());
+
+            let _ = Balances::make_free_balance_be(&1, 2 * MIN_STAKE);
+            let _ = Balances::make_free_balance_be(&2, 2 * MIN_STAKE);
+            assert_ok!(Providers::register(Origin::signed(1), multiaddr.clone(), 5, MIN_STAKE));
+            assert_noop!(
+                Providers::register(Origin::signed(1), multiaddr.clone(), 5, MIN_STAKE),
+                ERR_PROVIDER_REGISTERED
+            );
+            assert_ok!(Providers::register(Origin::signed(2), multiaddr.clone(), 7, MIN_STAKE));
+            assert_eq!(Providers::provider_of(&2), Some(1));
+            assert_eq!(Providers::provider_count(), 2);
+            assert_eq!(Balances::reserved_balance(&1), MIN_STAKE);
+            assert!(Providers::is_active(0));
+
+            assert_ok!(Providers::update(Origin::signed(1), vec![1], 6));
+            assert_ok!(Providers::bond_extra(Origin::signed(1), 100));
+            let provider = Providers::provider(0).unwrap();
+            assert_eq!(provider.multiaddr, vec![1]);
+            assert_eq!(provider.price, 6);
+            assert_eq!(provider.stake, MIN_STAKE + 100);
+            assert_eq!(Balances::reserved_balance(&1), MIN_STAKE + 100);
+            assert_noop!(Providers::bond_extra(Origin::signed(1), u64::max_value()), ERR_STAKE_OVERFLOW);
+        });
+    }
+
+    #[test]
+    fn unregister_and_withdraw_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let _ = Balances::make_free_balance_be(&1, MIN_STAKE);
+            assert_noop!(Providers::unregister(Origin::signed(1)), ERR_PROVIDER_NOT_EXIST);
+            assert_ok!(Providers::register(Origin::signed(1), vec![1], 5, MIN_STAKE));
+            assert_noop!(Providers::withdraw(Origin::signed(1)), ERR_PROVIDER_NOT_UNBONDING);
+
+            assert_ok!(Providers::unregister(Origin::signed(1)));
+            assert!(!Providers::is_active(0));
+            assert_noop!(Providers::update(Origin::signed(1), vec![2], 5), ERR_PROVIDER_UNBONDING);
+            assert_noop!(Providers::withdraw(Origin::signed(1)), ERR_UNBONDING_NOT_ENDED);
+
+            System::set_block_number(UNBONDING_PERIOD);
+            assert_ok!(Providers::withdraw(Origin::signed(1)));
+            assert_eq!(Providers::provider(0), None);
+            assert_eq!(Providers::provider_of(&1), None);
+            assert_eq!(Balances::free_balance(&1), MIN_STAKE);
+            assert_eq!(Balances::reserved_balance(&1), 0);
+        });
+    }
+
+    #[test]
+    fn slash_works() {
+        with_externalities(&mut new_test_ext(), || {
+            let _ = Balances::make_free_balance_be(&1, MIN_STAKE);
+            assert_ok!(Providers::register(Origin::signed(1), vec![1], 5, MIN_STAKE));
+            assert_noop!(Providers::slash(Origin::signed(2), 0, 100), federation::ERR_RANK_INSUFFICIENT);
+            assert_noop!(Providers::slash(Origin::ROOT, 1, 100), ERR_PROVIDER_NOT_EXIST);
+
+            assert_ok!(Providers::slash(Origin::ROOT, 0, 100));
+            assert_eq!(Providers::provider(0).unwrap().stake, MIN_STAKE - 100);
+            assert_eq!(Balances::reserved_balance(&1), MIN_STAKE - 100);
+            assert_eq!(Treasury::pot(), 100);
+            // below the minimum stake the provider can't be referenced anymore
+            assert!(!Providers::is_active(0));
+
+            // the stake caps the slash, even during unbonding
+            assert_ok!(Providers::unregister(Origin::signed(1)));
+            assert_ok!(Providers::slash(Origin::ROOT, 0, MIN_STAKE));
+            assert_eq!(Providers::provider(0).unwrap().stake, 0);
+            assert_eq!(Treasury::pot(), MIN_STAKE);
+        });
+    }
+}
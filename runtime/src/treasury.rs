@@ -0,0 +1,243 @@
+//! # Treasury Module
+//!
+//! The Treasury module collects the fees of unique names and unavailability reports as well as slashed stake in a pot instead of burning them.
+//! The pot is the free balance of an account without a private key, minus the existential deposit, which keeps the account alive.
+//! Funds are withdrawn from one account and deposited into the other, so the total issuance doesn't change and no transfer fees are charged.
+//! Chains upgraded from a runtime without the treasury get the pot account created with the existential deposit on the first deposit.
+//! Spends need root or a federation member with at least the admiral rank.
+
+use parity_codec::Decode;
+use runtime_primitives::traits::As;
+use support::{
+    decl_event, decl_module,
+    dispatch::Result,
+    ensure,
+    traits::{Currency, ExistenceRequirement, ReservableCurrency, WithdrawReason},
+};
+
+use crate::federation;
+
+const ERR_POT_LOW: &str = "The treasury pot is too low for this spend";
+const ERR_RECEIVER_BALANCE_LOW: &str = "The amount is too low to create the account of the receiver";
+
+/// Seed of the pot account
+const POT_ID: [u8; 32] = *b"starlog/treasury/pot\0\0\0\0\0\0\0\0\0\0\0\0";
+
+/// Minimum federation rank to spend from the pot
+const SPEND_RANK: u16 = federation::ADMIRAL_RANK;
+
+/// The module's configuration trait.
+pub trait Trait: balances::Trait + federation::Trait {
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+decl_module! {
+    /// The module declaration.
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        fn deposit_event<T>() = default;
+
+        /// Spend funds of the pot, only root or higher federation ranks
+        fn spend(origin, beneficiary: T::AccountId, amount: T::Balance) -> Result {
+            <federation::Module<T>>::ensure_root_or_rank(origin, SPEND_RANK)?;
+            ensure!(amount <= Self::pot(), ERR_POT_LOW);
+
+            Self::_pay_out(&beneficiary, amount)?;
+
+            Self::deposit_event(RawEvent::Spent(beneficiary, amount));
+            Ok(())
+        }
+    }
+}
+
+decl_event!(
+    pub enum Event<T> where
+        <T as system::Trait>::AccountId,
+        <T as balances::Trait>::Balance
+    {
+        /// amount added to the pot
+        Collected(Balance),
+        /// beneficiary, amount
+        Spent(AccountId, Balance),
+        /// account, refunded amount
+        Refunded(AccountId, Balance),
+    }
+);
+
+impl<T: Trait> Module<T> {
+    /// Account holding the pot, nobody has its private key
+    pub fn account_id() -> T::AccountId {
+        T::AccountId::decode(&mut &POT_ID[..]).unwrap_or_default()
+    }
+
+    /// Funds available for spending, the existential deposit keeps the pot account alive
+    pub fn pot() -> T::Balance {
+        let balance = <balances::Module<T>>::free_balance(&Self::account_id());
+        let existential_deposit = <balances::Module<T>>::existential_deposit();
+        if balance > existential_deposit {
+            balance - existential_deposit
+        } else {
+            T::Balance::sa(0)
+        }
+    }
+
+    /// Moves a fee from the account into the pot
+    pub fn collect_fee(who: &T::AccountId, fee: T::Balance) -> Result {
+        let _ = <balances::Module<T> as Currency<_>>::withdraw(
+            who,
+            fee,
+            WithdrawReason::Fee,
+            ExistenceRequirement::KeepAlive,
+        )?;
+        Self::_deposit_into_pot(fee);
+        Self::deposit_event(RawEvent::Collected(fee));
+        Ok(())
+    }
+
+    /// Moves reserved funds of the account into the pot, e.g. slashed stake, and returns the moved amount
+    pub fn slash_reserved(who: &T::AccountId, amount: T::Balance) -> T::Balance {
+        let pot_account = Self::account_id();
+        let moved = match <balances::Module<T> as ReservableCurrency<_>>::repatriate_reserved(who, &pot_account, amount) {
+            Ok(remaining) => amount - remaining,
+            // the pot account hasn't been created yet, the slashed funds create it
+            Err(_) => {
+                let (_, remaining) = <balances::Module<T> as ReservableCurrency<_>>::slash_reserved(who, amount);
+                let slashed = amount - remaining;
+                Self::_deposit_into_pot(slashed);
+                slashed
+            }
+        };
+        if moved > T::Balance::sa(0) {
+            Self::deposit_event(RawEvent::Collected(moved));
+        }
+        moved
+    }
+
+    /// Pays back up to the given amount out of the pot and returns the paid amount
+    /// Nothing is paid, if the amount is too small to create the account.
+    pub fn refund(who: &T::AccountId, amount: T::Balance) -> T::Balance {
+        let pot = Self::pot();
+        let paid = if amount > pot { pot } else { amount };
+        if paid == T::Balance::sa(0) {
+            return paid;
+        }
+        if Self::_pay_out(who, paid).is_err() {
+            return T::Balance::sa(0);
+        }
+
+        Self::deposit_event(RawEvent::Refunded(who.clone(), paid));
+        paid
+    }
+
+    /// Deposits into the pot account, which is created with the existential deposit first if it doesn't exist yet
+    fn _deposit_into_pot(amount: T::Balance) {
+        let pot_account = Self::account_id();
+        if <balances::Module<T>>::free_balance(&pot_account) == T::Balance::sa(0) {
+            let existential_deposit = <balances::Module<T>>::existential_deposit();
+            let _ = <balances::Module<T> as Currency<_>>::deposit_creating(&pot_account, existential_deposit);
+        }
+        let _ = <balances::Module<T> as Currency<_>>::deposit_creating(&pot_account, amount);
+    }
+
+    /// Moves funds out of the pot, which is kept alive
+    fn _pay_out(who: &T::AccountId, amount: T::Balance) -> Result {
+        // a deposit below the existential deposit wouldn't create the account and be lost
+        ensure!(
+            <balances::Module<T>>::free_balance(who) > T::Balance::sa(0)
+                || amount >= <balances::Module<T>>::existential_deposit(),
+            ERR_RECEIVER_BALANCE_LOW
+        );
+        let _ = <balances::Module<T> as Currency<_>>::withdraw(
+            &Self::account_id(),
+            amount,
+            WithdrawReason::Transfer,
+            ExistenceRequirement::KeepAlive,
+        )?;
+        let _ = <balances::Module<T> as Currency<_>>::deposit_creating(who, amount);
+        Ok(())
+    }
+}
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock::{new_test_ext_with, Origin, Test};
+    use runtime_io::with_externalities;
+    use support::{assert_noop, assert_ok};
+
+    type Balances = balances::Module<Test>;
+    type Treasury = Module<Test>;
+
+    #[test]
+    fn collect_fee_works() {
+        with_externalities(&mut new_test_ext_with(0), || {
+            let _ = Balances::make_free_balance_be(&0, 1000);
+            assert_ok!(Treasury::collect_fee(&0, 300));
+            assert_eq!(Balances::free_balance(&0), 700);
+            assert_eq!(Treasury::pot(), 300);
+            assert_eq!(Treasury::refund(&0, 500), 300);
+            assert_eq!(Balances::free_balance(&0), 1000);
+            assert_eq!(Treasury::pot(), 0);
+        });
+    }
+
+    #[test]
+    fn spend_works() {
+        with_externalities(&mut new_test_ext_with(0), || {
+            let _ = Balances::make_free_balance_be(&Treasury::account_id(), 300);
+            assert_noop!(
+                Treasury::spend(Origin::signed(0), 1, 100),
+                federation::ERR_RANK_INSUFFICIENT
+            );
+            assert_noop!(Treasury::spend(Origin::ROOT, 1, 301), ERR_POT_LOW);
+            assert_ok!(Treasury::spend(Origin::ROOT, 1, 100));
+            assert_eq!(Balances::free_balance(&1), 100);
+            assert_eq!(Treasury::pot(), 200);
+        });
+    }
+
+    #[test]
+    fn existential_deposit_works() {
+        with_externalities(&mut new_test_ext_with(500), || {
+            let _ = Balances::make_free_balance_be(&Treasury::account_id(), 800);
+            assert_eq!(Treasury::pot(), 300);
+            let _ = Balances::make_free_balance_be(&0, 1000);
+            assert_ok!(Treasury::collect_fee(&0, 100));
+            assert_eq!(Treasury::pot(), 400);
+
+            // amounts too small to create the account of the receiver stay in the pot
+            assert_noop!(Treasury::spend(Origin::ROOT, 1, 100), ERR_RECEIVER_BALANCE_LOW);
+            assert_eq!(Treasury::refund(&1, 100), 0);
+            assert_eq!(Balances::free_balance(&1), 0);
+            assert_eq!(Treasury::pot(), 400);
+            assert_eq!(Treasury::refund(&0, 100), 100);
+            assert_eq!(Balances::free_balance(&0), 1000);
+
+            // the existential deposit of the pot account can't be spent
+            assert_noop!(Treasury::spend(Origin::ROOT, 0, 301), ERR_POT_LOW);
+            assert_ok!(Treasury::spend(Origin::ROOT, 0, 300));
+            assert_eq!(Balances::free_balance(&Treasury::account_id()), 500);
+        });
+    }
+
+    #[test]
+    fn missing_pot_account_works() {
+        with_externalities(&mut new_test_ext_with(500), || {
+            // a chain upgraded without the pot account collects fees below the existential deposit
+            let _ = Balances::make_free_balance_be(&0, 1000);
+            let issuance = Balances::total_issuance();
+            assert_ok!(Treasury::collect_fee(&0, 100));
+            assert_eq!(Balances::free_balance(&0), 900);
+            assert_eq!(Treasury::pot(), 100);
+            assert_eq!(Balances::free_balance(&Treasury::account_id()), 600);
+            assert_eq!(Balances::total_issuance(), issuance + 500);
+
+            // the fee payer and the pot are kept alive
+            assert!(Treasury::collect_fee(&0, 500).is_err());
+            assert_ok!(Treasury::spend(Origin::ROOT, 0, 100));
+            assert_eq!(Treasury::pot(), 0);
+            assert_eq!(Balances::free_balance(&Treasury::account_id()), 500);
+        });
+    }
+}
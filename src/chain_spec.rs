@@ -1,7 +1,8 @@
 use primitives::{ed25519, sr25519, Pair};
 use starlog_runtime::{
     AccountId, BalancesConfig, ConsensusConfig,  GenesisConfig, IndicesConfig,
-    SudoConfig, TimestampConfig, ContractConfig, FederationConfig,
+    SudoConfig, TimestampConfig, ContractConfig, FederationConfig, MetalogConfig,
+    Runtime, treasury,
 };
 
 use substrate_service;
@@ -92,6 +93,8 @@ impl Alternative {
     }
 }
 
+const EXISTENTIAL_DEPOSIT: u128 = 500;
+
 fn testnet_genesis(
     initial_authorities: Vec<AuthorityId>,
     endowed_accounts: Vec<AccountId>,
@@ -112,10 +115,13 @@ fn testnet_genesis(
 		balances: Some(BalancesConfig {
 			transaction_base_fee: 1,
 			transaction_byte_fee: 1,
-			existential_deposit: 500,
+			existential_deposit: EXISTENTIAL_DEPOSIT,
 			transfer_fee: 0,
 			creation_fee: 0,
-			balances: endowed_accounts.iter().cloned().map(|k|(k, 1 << 60)).collect(),
+			balances: endowed_accounts.iter().cloned().map(|k|(k, 1 << 60))
+				// the treasury pot account exists from genesis, so fees below the existential deposit can be collected
+				.chain(Some((treasury::Module::<Runtime>::account_id(), EXISTENTIAL_DEPOSIT)))
+				.collect(),
 			vesting: vec![],
 		}),
 		sudo: Some(SudoConfig {
@@ -144,6 +150,11 @@ fn testnet_genesis(
             min_lock: 100800,
             rank_lock: 403200,
             challenge_lock: 100800,
-		})
+		}),
+		metalog: Some(MetalogConfig {
+			fee_per_used_char: 100,
+			name_limit: 50,
+			short_name_length: 5,
+		}),
 	}
 }
@@ -0,0 +1,21 @@
+//! Reading the runtime events of a block from the chain state.
+
+use parity_codec::Decode;
+use primitives::{storage::StorageKey, twox_128, Blake2Hasher};
+use starlog_runtime::{
+	opaque::{Block, BlockId},
+	EventRecord, Hash,
+};
+use substrate_client::{backend::Backend, CallExecutor, Client};
+
+/// Events deposited in the block, fails if the state of the block has been pruned
+pub fn events_at<B, E, RA>(client: &Client<B, E, Block, RA>, hash: Hash) -> Result<Vec<EventRecord>, String> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+{
+	let events_key = StorageKey(twox_128(b"System Events").to_vec());
+	match client.storage(&BlockId::hash(hash), &events_key).map_err(|e| format!("{:?}", e))? {
+		Some(data) => Vec::<EventRecord>::decode(&mut &data.0[..]).ok_or_else(|| "Unable to decode the events".into()),
+		None => Ok(Vec::new()),
+	}
+}
@@ -0,0 +1,454 @@
+//! Indexer of metalog and federation events into an embedded SQLite database.
+//!
+//! Aura doesn't finalize blocks, so the indexer follows the best chain. Every row references
+//! the hash of the block its event was deposited in. On a reorg the rows of the retracted blocks
+//! are deleted, before the blocks of the new best chain are indexed.
+//! Indexing older blocks needs their state, nodes with state pruning should run with `--pruning archive`.
+//! The database only holds data derived from the chain, it is dropped and rebuilt when the schema changes.
+
+use std::{path::Path, sync::Arc};
+
+use futures::{future, Future, Stream};
+use log::{info, warn};
+use parking_lot::Mutex;
+use primitives::{crypto::Ss58Codec, Blake2Hasher};
+use rusqlite::{types::ToSql, Connection, Transaction, NO_PARAMS};
+use starlog_runtime::{
+	federation::RawEvent as FederationEvent,
+	metalog::RawEvent as MetalogEvent,
+	opaque::{Block, BlockId, Header},
+	AccountId, Event, Hash, Runtime,
+};
+use substrate_client::{
+	backend::Backend, blockchain::tree_route, BlockchainEvents, CallExecutor, Client,
+};
+
+use crate::events::events_at;
+
+/// License code of delete requests
+const DELETE_LICENSE: i64 = 1;
+
+/// Version of the schema, stored as `user_version` of the database
+const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+	CREATE TABLE IF NOT EXISTS blocks (
+		number INTEGER NOT NULL,
+		hash BLOB PRIMARY KEY,
+		parent_hash BLOB NOT NULL
+	);
+	CREATE TABLE IF NOT EXISTS dids (
+		did BLOB NOT NULL,
+		owner TEXT NOT NULL,
+		time INTEGER NOT NULL,
+		file_hash BLOB NOT NULL,
+		price TEXT NOT NULL,
+		meta_hash BLOB NOT NULL,
+		provider INTEGER,
+		block_hash BLOB NOT NULL,
+		event_index INTEGER NOT NULL
+	);
+	CREATE TABLE IF NOT EXISTS names (
+		name BLOB NOT NULL,
+		did BLOB,
+		owner TEXT,
+		action TEXT NOT NULL,
+		amount TEXT,
+		block_hash BLOB NOT NULL,
+		event_index INTEGER NOT NULL
+	);
+	CREATE TABLE IF NOT EXISTS owners (
+		did BLOB NOT NULL,
+		owner TEXT NOT NULL,
+		block_hash BLOB NOT NULL,
+		event_index INTEGER NOT NULL
+	);
+	CREATE TABLE IF NOT EXISTS licenses (
+		did BLOB,
+		license_code INTEGER NOT NULL,
+		spdx BLOB,
+		block_hash BLOB NOT NULL,
+		event_index INTEGER NOT NULL
+	);
+	CREATE TABLE IF NOT EXISTS transfers (
+		did BLOB NOT NULL,
+		sender TEXT NOT NULL,
+		receiver TEXT NOT NULL,
+		block_hash BLOB NOT NULL,
+		event_index INTEGER NOT NULL
+	);
+	CREATE TABLE IF NOT EXISTS federation (
+		account TEXT NOT NULL,
+		action TEXT NOT NULL,
+		rank INTEGER,
+		amount TEXT,
+		block_hash BLOB NOT NULL,
+		event_index INTEGER NOT NULL
+	);
+	CREATE INDEX IF NOT EXISTS dids_did ON dids (did);
+	CREATE INDEX IF NOT EXISTS names_name ON names (name);
+	CREATE INDEX IF NOT EXISTS owners_did ON owners (did);
+	CREATE INDEX IF NOT EXISTS owners_owner ON owners (owner);
+	CREATE INDEX IF NOT EXISTS transfers_did ON transfers (did);
+";
+
+/// Tables with rows referencing the block of their event
+const EVENT_TABLES: [&str; 6] = ["dids", "names", "owners", "licenses", "transfers", "federation"];
+
+/// SQLite indexer of the best chain
+pub struct Indexer<B, E, RA> {
+	connection: Mutex<Connection>,
+	client: Arc<Client<B, E, Block, RA>>,
+}
+
+impl<B, E, RA> Indexer<B, E, RA> where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+{
+	/// Opens or creates the database at the path
+	pub fn open(path: &Path, client: Arc<Client<B, E, Block, RA>>) -> Result<Self, String> {
+		let connection = Connection::open(path).map_err(|e| e.to_string())?;
+		migrate(&connection)?;
+		Ok(Indexer {
+			connection: Mutex::new(connection),
+			client,
+		})
+	}
+
+	/// Catches up with the best block, then follows new best blocks until the node exits
+	pub fn follow(self: Arc<Self>) -> impl Future<Item = (), Error = ()> {
+		let notifications = self.client.import_notification_stream();
+		let indexer = self.clone();
+		future::lazy(move || {
+			match indexer.client.info() {
+				Ok(info) => indexer.sync(info.chain.best_hash),
+				Err(e) => warn!("Unable to read the best block: {:?}", e),
+			}
+			Ok(())
+		}).and_then(move |_| notifications.for_each(move |notification| {
+			if notification.is_new_best {
+				self.sync(notification.hash);
+			}
+			Ok(())
+		}))
+	}
+
+	fn sync(&self, best: Hash) {
+		if let Err(e) = self.sync_to(best) {
+			warn!("Unable to index block {}: {}", best, e);
+		}
+	}
+
+	/// Rolls back the blocks, which aren't part of the new best chain, and indexes the new ones
+	fn sync_to(&self, best: Hash) -> Result<(), String> {
+		let mut connection = self.connection.lock();
+		let transaction = connection.transaction().map_err(|e| e.to_string())?;
+
+		let enacted = match tip(&transaction)? {
+			Some(tip) if tip == best => return Ok(()),
+			Some(tip) => {
+				let route = tree_route(self.client.backend().blockchain(), BlockId::hash(tip), BlockId::hash(best))
+					.map_err(|e| format!("{:?}", e))?;
+				for retracted in route.retracted() {
+					rollback(&transaction, retracted.hash)?;
+				}
+				route.enacted().iter().map(|entry| entry.hash).collect()
+			}
+			None => self.canonical_blocks(best)?,
+		};
+		if enacted.len() > 1 {
+			info!("Indexing {} blocks", enacted.len());
+		}
+
+		for hash in enacted {
+			self.index_block(&transaction, hash)?;
+		}
+		transaction.commit().map_err(|e| e.to_string())
+	}
+
+	/// Hashes of the best chain from block 1 up to the best block
+	fn canonical_blocks(&self, best: Hash) -> Result<Vec<Hash>, String> {
+		let best_number = self.header_of(best)?.number;
+		let mut hashes = Vec::new();
+		for number in 1..=best_number {
+			match self.client.block_hash(number).map_err(|e| format!("{:?}", e))? {
+				Some(hash) => hashes.push(hash),
+				None => return Err(format!("Block {} is unknown", number)),
+			}
+		}
+		Ok(hashes)
+	}
+
+	fn header_of(&self, hash: Hash) -> Result<Header, String> {
+		self.client.header(&BlockId::hash(hash))
+			.map_err(|e| format!("{:?}", e))?
+			.ok_or_else(|| format!("Block {} is unknown", hash))
+	}
+
+	/// Writes the block and the rows of its metalog and federation events
+	fn index_block(&self, transaction: &Transaction, hash: Hash) -> Result<(), String> {
+		let header = self.header_of(hash)?;
+		let events = match events_at(&self.client, hash) {
+			Ok(records) => records.into_iter().map(|record| record.event).collect(),
+			Err(e) => {
+				warn!("Skipping the events of block {}, run the node with --pruning archive to index them: {}", hash, e);
+				Vec::new()
+			}
+		};
+		write_block(transaction, header.number, hash, header.parent_hash, events)
+	}
+}
+
+/// Writes the block and the rows of the events deposited in it
+fn write_block(transaction: &Transaction, number: u64, hash: Hash, parent_hash: Hash, events: Vec<Event>) -> Result<(), String> {
+	let block_hash = hash.as_ref().to_vec();
+	transaction.execute(
+		"INSERT OR REPLACE INTO blocks (number, hash, parent_hash) VALUES (?1, ?2, ?3)",
+		&[&(number as i64) as &ToSql, &block_hash, &parent_hash.as_ref().to_vec()],
+	).map_err(|e| e.to_string())?;
+
+	for (index, event) in events.into_iter().enumerate() {
+		let row = Row { transaction, block_hash: &block_hash, event_index: index as i64 };
+		match event {
+			Event::metalog(event) => row.metalog(event)?,
+			Event::federation(event) => row.federation(event)?,
+			_ => {}
+		}
+	}
+	Ok(())
+}
+
+/// Insertion of the rows of one event
+struct Row<'a, 'b> {
+	transaction: &'a Transaction<'b>,
+	block_hash: &'a Vec<u8>,
+	event_index: i64,
+}
+
+impl<'a, 'b> Row<'a, 'b> {
+	/// Inserts the values followed by the block hash and the event index
+	fn insert(&self, table: &str, columns: &str, values: &[&ToSql]) -> Result<(), String> {
+		let mut params = values.to_vec();
+		params.push(self.block_hash);
+		params.push(&self.event_index);
+		let placeholders = (1..=params.len()).map(|i| format!("?{}", i)).collect::<Vec<_>>().join(", ");
+
+		let sql = format!("INSERT INTO {} ({}, block_hash, event_index) VALUES ({})", table, columns, placeholders);
+		self.transaction.execute(&sql, &params).map(|_| ()).map_err(|e| e.to_string())
+	}
+
+	fn name(&self, name: Vec<u8>, did: Option<Vec<u8>>, owner: Option<AccountId>, action: &str, amount: Option<String>) -> Result<(), String> {
+		let owner = owner.map(|owner| owner.to_ss58check());
+		self.insert("names", "name, did, owner, action, amount", &[&name, &did, &owner, &action, &amount])
+	}
+
+	fn metalog(&self, event: starlog_runtime::metalog::Event<Runtime>) -> Result<(), String> {
+		match event {
+			MetalogEvent::Stored(owner, time, did, file_hash, price, meta_hash, provider) => {
+				let owner = owner.to_ss58check();
+				self.insert(
+					"dids",
+					"did, owner, time, file_hash, price, meta_hash, provider",
+					&[&did, &owner, &(time as i64), &file_hash, &price.to_string(), &meta_hash, &provider.map(|id| id as i64)],
+				)?;
+				self.insert("owners", "did, owner", &[&did, &owner])
+			}
+			MetalogEvent::TransferOwnership(sender, receiver, did) => {
+				let receiver = receiver.to_ss58check();
+				self.insert("transfers", "did, sender, receiver", &[&did, &sender.to_ss58check(), &receiver])?;
+				self.insert("owners", "did, owner", &[&did, &receiver])
+			}
+			MetalogEvent::LicenseUpdated(_, did, license_code) => {
+				self.insert("licenses", "did, license_code", &[&did, &(license_code as i64)])
+			}
+			MetalogEvent::LicenseRegistered(license_code, spdx) => {
+				self.insert("licenses", "license_code, spdx", &[&(license_code as i64), &spdx])
+			}
+			MetalogEvent::DeleteRequested(_, did, _) => {
+				self.insert("licenses", "did, license_code", &[&did, &DELETE_LICENSE])
+			}
+			MetalogEvent::NameUpdated(owner, did, name, fee) => {
+				self.name(name, Some(did), Some(owner), "updated", Some(fee.to_string()))
+			}
+			MetalogEvent::NameRenewed(owner, name, _, fee) => {
+				self.name(name, None, Some(owner), "renewed", Some(fee.to_string()))
+			}
+			MetalogEvent::NameExpired(name) => self.name(name, None, None, "expired", None),
+			MetalogEvent::NameRepointed(owner, name, did) => self.name(name, Some(did), Some(owner), "repointed", None),
+			MetalogEvent::NameTransferred(_, receiver, name) => self.name(name, None, Some(receiver), "transferred", None),
+			// also emitted for the name of a deleted DID, before DeleteRequested
+			MetalogEvent::NameReleased(owner, did, name, refund) => {
+				self.name(name, Some(did), Some(owner), "released", Some(refund.to_string()))
+			}
+			MetalogEvent::NamespacedNameSet(publisher, name, did) => {
+				self.name(name, Some(did), Some(publisher), "namespace_set", None)
+			}
+			MetalogEvent::NamespacedNameRemoved(publisher, name) => {
+				self.name(name, None, Some(publisher), "namespace_removed", None)
+			}
+			_ => Ok(()),
+		}
+	}
+
+	fn federation(&self, event: starlog_runtime::federation::Event<Runtime>) -> Result<(), String> {
+		let (account, action, rank, amount) = match event {
+			FederationEvent::CandidateStored(account, rank) => (account, "candidate_stored", Some(rank as i64), None),
+			FederationEvent::CandidateCanceled(account, rank) => (account, "candidate_canceled", Some(rank as i64), None),
+			FederationEvent::Voted(account, amount) => (account, "voted", None, Some(amount.to_string())),
+			FederationEvent::Challenged(account, amount) => (account, "challenged", None, Some(amount.to_string())),
+			FederationEvent::CancelVote(account, amount) => (account, "vote_canceled", None, Some(amount.to_string())),
+		};
+		self.insert("federation", "account, action, rank, amount", &[&account.to_ss58check(), &action, &rank, &amount])
+	}
+}
+
+/// Creates the tables, a database of another schema version is dropped and indexed again
+fn migrate(connection: &Connection) -> Result<(), String> {
+	let version: i64 = connection
+		.query_row("PRAGMA user_version", NO_PARAMS, |row| row.get(0))
+		.map_err(|e| e.to_string())?;
+	if version != SCHEMA_VERSION {
+		if version != 0 || tables_exist(connection)? {
+			info!("Rebuilding the index of schema version {} with schema version {}", version, SCHEMA_VERSION);
+		}
+		for table in EVENT_TABLES.iter().chain(["blocks"].iter()) {
+			connection
+				.execute(&format!("DROP TABLE IF EXISTS {}", table), NO_PARAMS)
+				.map_err(|e| e.to_string())?;
+		}
+	}
+	connection.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+	connection
+		.execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))
+		.map_err(|e| e.to_string())
+}
+
+/// Whether an earlier version created the blocks table
+fn tables_exist(connection: &Connection) -> Result<bool, String> {
+	let count: i64 = connection
+		.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'blocks'", NO_PARAMS, |row| row.get(0))
+		.map_err(|e| e.to_string())?;
+	Ok(count > 0)
+}
+
+/// Hash of the highest indexed block
+fn tip(transaction: &Transaction) -> Result<Option<Hash>, String> {
+	let mut statement = transaction
+		.prepare("SELECT hash FROM blocks ORDER BY number DESC LIMIT 1")
+		.map_err(|e| e.to_string())?;
+	let mut rows = statement.query(NO_PARAMS).map_err(|e| e.to_string())?;
+	match rows.next() {
+		Some(row) => {
+			let hash: Vec<u8> = row.map_err(|e| e.to_string())?.get(0);
+			Ok(Some(Hash::from_slice(&hash)))
+		}
+		None => Ok(None),
+	}
+}
+
+/// Deletes the block and the rows of its events
+fn rollback(transaction: &Transaction, hash: Hash) -> Result<(), String> {
+	let block_hash = hash.as_ref().to_vec();
+	for table in EVENT_TABLES.iter() {
+		transaction
+			.execute(&format!("DELETE FROM {} WHERE block_hash = ?1", table), &[&block_hash as &ToSql])
+			.map_err(|e| e.to_string())?;
+	}
+	transaction
+		.execute("DELETE FROM blocks WHERE hash = ?1", &[&block_hash as &ToSql])
+		.map_err(|e| e.to_string())?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn count(transaction: &Transaction, sql: &str) -> i64 {
+		transaction.query_row(sql, NO_PARAMS, |row| row.get(0)).unwrap()
+	}
+
+	fn stored(owner: &AccountId, did: &[u8]) -> Event {
+		Event::metalog(MetalogEvent::Stored(owner.clone(), 0, did.to_vec(), vec![1], 10, vec![2], None))
+	}
+
+	fn named(owner: &AccountId, did: &[u8], name: &[u8]) -> Event {
+		Event::metalog(MetalogEvent::NameUpdated(owner.clone(), did.to_vec(), name.to_vec(), 100))
+	}
+
+	#[test]
+	fn write_block_works() {
+		let mut connection = Connection::open_in_memory().unwrap();
+		migrate(&connection).unwrap();
+		let transaction = connection.transaction().unwrap();
+		let owner = AccountId::default();
+		let block_1 = Hash::repeat_byte(1);
+
+		write_block(&transaction, 1, block_1, Hash::zero(), vec![stored(&owner, b"did"), named(&owner, b"did", b"name")]).unwrap();
+		assert_eq!(tip(&transaction).unwrap(), Some(block_1));
+		assert_eq!(count(&transaction, "SELECT COUNT(*) FROM dids WHERE did = X'646964' AND price = '10'"), 1);
+		assert_eq!(count(&transaction, "SELECT COUNT(*) FROM owners WHERE did = X'646964'"), 1);
+		assert_eq!(count(&transaction, "SELECT COUNT(*) FROM names WHERE name = X'6E616D65' AND action = 'updated' AND event_index = 1"), 1);
+	}
+
+	#[test]
+	fn reorg_rolls_back_retracted_blocks() {
+		let mut connection = Connection::open_in_memory().unwrap();
+		migrate(&connection).unwrap();
+		let transaction = connection.transaction().unwrap();
+		let owner = AccountId::default();
+		let (block_1, block_2, fork_2) = (Hash::repeat_byte(1), Hash::repeat_byte(2), Hash::repeat_byte(3));
+		write_block(&transaction, 1, block_1, Hash::zero(), vec![stored(&owner, b"did")]).unwrap();
+		write_block(&transaction, 2, block_2, block_1, vec![named(&owner, b"did", b"name")]).unwrap();
+
+		// the tree route from block 2 to the fork retracts block 2 and enacts the fork
+		rollback(&transaction, block_2).unwrap();
+		assert_eq!(tip(&transaction).unwrap(), Some(block_1));
+		assert_eq!(count(&transaction, "SELECT COUNT(*) FROM names"), 0);
+		write_block(&transaction, 2, fork_2, block_1, vec![named(&owner, b"did", b"other")]).unwrap();
+		assert_eq!(tip(&transaction).unwrap(), Some(fork_2));
+		assert_eq!(count(&transaction, "SELECT COUNT(*) FROM names WHERE name = X'6F74686572'"), 1);
+		assert_eq!(count(&transaction, "SELECT COUNT(*) FROM dids"), 1);
+	}
+
+	#[test]
+	fn delete_request_releases_the_name() {
+		let mut connection = Connection::open_in_memory().unwrap();
+		migrate(&connection).unwrap();
+		let transaction = connection.transaction().unwrap();
+		let owner = AccountId::default();
+		let (block_1, block_2) = (Hash::repeat_byte(1), Hash::repeat_byte(2));
+		write_block(&transaction, 1, block_1, Hash::zero(), vec![stored(&owner, b"did"), named(&owner, b"did", b"name")]).unwrap();
+
+		let released = MetalogEvent::NameReleased(owner.clone(), b"did".to_vec(), b"name".to_vec(), 0);
+		let deleted = MetalogEvent::DeleteRequested(owner.clone(), b"did".to_vec(), 2);
+		write_block(&transaction, 2, block_2, block_1, vec![Event::metalog(released), Event::metalog(deleted)]).unwrap();
+		assert_eq!(count(&transaction, "SELECT COUNT(*) FROM names WHERE name = X'6E616D65' AND action = 'released' AND amount = '0'"), 1);
+		assert_eq!(count(&transaction, "SELECT COUNT(*) FROM licenses WHERE did = X'646964' AND license_code = 1"), 1);
+	}
+
+	#[test]
+	fn migrate_rebuilds_older_schemas() {
+		let connection = Connection::open_in_memory().unwrap();
+		// the dids table of the first schema had a location instead of a provider column
+		connection.execute_batch("
+			CREATE TABLE blocks (number INTEGER NOT NULL, hash BLOB PRIMARY KEY, parent_hash BLOB NOT NULL);
+			CREATE TABLE dids (did BLOB NOT NULL, location BLOB);
+			INSERT INTO blocks (number, hash, parent_hash) VALUES (1, X'01', X'00');
+		").unwrap();
+
+		migrate(&connection).unwrap();
+		let version: i64 = connection.query_row("PRAGMA user_version", NO_PARAMS, |row| row.get(0)).unwrap();
+		assert_eq!(version, SCHEMA_VERSION);
+		let blocks: i64 = connection.query_row("SELECT COUNT(*) FROM blocks", NO_PARAMS, |row| row.get(0)).unwrap();
+		assert_eq!(blocks, 0);
+		connection.prepare("SELECT provider FROM dids").unwrap();
+
+		// reopening the current schema keeps the index
+		connection.execute("INSERT INTO blocks (number, hash, parent_hash) VALUES (1, X'01', X'00')", NO_PARAMS).unwrap();
+		migrate(&connection).unwrap();
+		let blocks: i64 = connection.query_row("SELECT COUNT(*) FROM blocks", NO_PARAMS, |row| row.get(0)).unwrap();
+		assert_eq!(blocks, 1);
+	}
+}
@@ -5,6 +5,11 @@
 
 mod chain_spec;
 mod cli;
+mod events;
+mod indexer;
+mod name_index;
+mod probe;
+mod rpc;
 mod service;
 
 pub use substrate_cli::{error, IntoExit, VersionInfo};
@@ -0,0 +1,237 @@
+//! Search index of unique names, kept by full nodes in their own database.
+//!
+//! Names are stored case-folded in a RocksDB database next to the chain database,
+//! so they can be searched by prefix regardless of case. On startup the index is rebuilt
+//! from the names in the chain state, afterwards it follows the name events of new best blocks.
+//! Search results are always looked up again at the best block, entries of retracted blocks
+//! are dropped there.
+
+use std::{io, path::Path, sync::Arc};
+
+use futures::{future, Future, Stream};
+use kvdb::{DBTransaction, KeyValueDB};
+use kvdb_rocksdb::{Database, DatabaseConfig};
+use log::{info, warn};
+use parking_lot::Mutex;
+use primitives::Blake2Hasher;
+use starlog_runtime::{
+	metalog::RawEvent as MetalogEvent,
+	opaque::{Block, BlockId},
+	Event, Hash, MetalogApi,
+};
+use substrate_client::{
+	backend::Backend, runtime_api::ProvideRuntimeApi, BlockchainEvents, CallExecutor, Client,
+};
+
+use crate::events::events_at;
+
+const COLUMN_NAMES: Option<u32> = Some(0);
+const NUM_COLUMNS: u32 = 1;
+
+/// Names requested per runtime API call during a rebuild
+const REBUILD_PAGE_SIZE: u64 = 100;
+
+/// Change of a unique name found in the events of a block
+#[cfg_attr(test, derive(Debug, PartialEq))]
+enum NameChange {
+	Claimed(Vec<u8>),
+	Removed(Vec<u8>),
+}
+
+/// Unique name index, case-insensitive prefix search over all claimed names
+pub struct NameIndex<B, E, RA> {
+	db: Database,
+	client: Arc<Client<B, E, Block, RA>>,
+	/// Last best block, whose events have been indexed
+	indexed: Mutex<Option<Hash>>,
+}
+
+impl<B, E, RA> NameIndex<B, E, RA> where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+	Client<B, E, Block, RA>: ProvideRuntimeApi,
+	<Client<B, E, Block, RA> as ProvideRuntimeApi>::Api: MetalogApi<Block>,
+{
+	/// Opens or creates the index database at the path
+	pub fn open(path: &Path, client: Arc<Client<B, E, Block, RA>>) -> io::Result<Self> {
+		let config = DatabaseConfig::with_columns(Some(NUM_COLUMNS));
+		let path = path.to_str().ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Invalid database path"))?;
+		Ok(NameIndex {
+			db: Database::open(&config, path)?,
+			client,
+			indexed: Mutex::new(None),
+		})
+	}
+
+	/// Visits the claimed unique names, whose case-folded form starts with the case-folded prefix,
+	/// until the visitor returns false
+	pub fn search<F: FnMut(Vec<u8>) -> bool>(&self, prefix: &[u8], visit: F) {
+		search(&self.db, prefix, visit)
+	}
+
+	/// Rebuilds the index, then follows new best blocks until the node exits
+	pub fn follow(self: Arc<Self>) -> impl Future<Item = (), Error = ()> {
+		let notifications = self.client.import_notification_stream();
+		let index = self.clone();
+		future::lazy(move || {
+			if let Err(e) = index.rebuild() {
+				warn!("Unable to build the name index: {}", e);
+			}
+			Ok(())
+		}).and_then(move |_| notifications.for_each(move |notification| {
+			if notification.is_new_best {
+				if let Err(e) = self.import(notification.hash, notification.header.parent_hash) {
+					warn!("Unable to index the names of block {}: {}", notification.hash, e);
+				}
+			}
+			Ok(())
+		}))
+	}
+
+	/// Replaces the index with the unique names in the state of the best block
+	fn rebuild(&self) -> Result<(), String> {
+		let best = self.client.info().map_err(|e| format!("{:?}", e))?.chain.best_hash;
+		let at = BlockId::hash(best);
+		let api = self.client.runtime_api();
+
+		let mut transaction = DBTransaction::new();
+		for (key, _) in self.db.iter(COLUMN_NAMES) {
+			transaction.delete(COLUMN_NAMES, &key);
+		}
+		let mut start = 0;
+		loop {
+			let names = api.unique_names(&at, start, REBUILD_PAGE_SIZE).map_err(|e| format!("{:?}", e))?;
+			for name in &names {
+				transaction.put(COLUMN_NAMES, &key(name), name);
+			}
+			start += names.len() as u64;
+			if (names.len() as u64) < REBUILD_PAGE_SIZE {
+				break;
+			}
+		}
+		self.db.write(transaction).map_err(|e| e.to_string())?;
+
+		info!("Name index rebuilt at block {} with {} names", best, start);
+		*self.indexed.lock() = Some(best);
+		Ok(())
+	}
+
+	/// Indexes the name events of a new best block, a reorg or an unreadable block rebuilds the index
+	fn import(&self, hash: Hash, parent_hash: Hash) -> Result<(), String> {
+		if *self.indexed.lock() != Some(parent_hash) {
+			return self.rebuild();
+		}
+		let changes = match self.changes(hash) {
+			Ok(changes) => changes,
+			Err(_) => return self.rebuild(),
+		};
+
+		let mut transaction = DBTransaction::new();
+		for change in changes {
+			match change {
+				NameChange::Claimed(name) => transaction.put(COLUMN_NAMES, &key(&name), &name),
+				NameChange::Removed(name) => transaction.delete(COLUMN_NAMES, &key(&name)),
+			}
+		}
+		self.db.write(transaction).map_err(|e| e.to_string())?;
+
+		*self.indexed.lock() = Some(hash);
+		Ok(())
+	}
+
+	/// Unique name changes in the events of the block
+	fn changes(&self, hash: Hash) -> Result<Vec<NameChange>, String> {
+		Ok(events_at(&self.client, hash)?.into_iter().filter_map(|record| name_change(record.event)).collect())
+	}
+}
+
+/// Visits the names of the database in key order, as long as their key starts with the case-folded prefix
+fn search<F: FnMut(Vec<u8>) -> bool>(db: &Database, prefix: &[u8], mut visit: F) {
+	let prefix = fold(prefix);
+	// the iterator continues past the prefix until the end of the column
+	for (key, name) in db.iter_from_prefix(COLUMN_NAMES, &prefix) {
+		if !key.starts_with(&prefix) || !visit(name.into_vec()) {
+			break;
+		}
+	}
+}
+
+/// Unique name change of an event
+fn name_change(event: Event) -> Option<NameChange> {
+	match event {
+		Event::metalog(MetalogEvent::NameUpdated(_, _, name, _)) => Some(NameChange::Claimed(name)),
+		// auctioned names become searchable once they point to a DID
+		Event::metalog(MetalogEvent::NameRepointed(_, name, _)) => Some(NameChange::Claimed(name)),
+		// also emitted for the name of a deleted DID
+		Event::metalog(MetalogEvent::NameReleased(_, _, name, _)) => Some(NameChange::Removed(name)),
+		Event::metalog(MetalogEvent::NameExpired(name)) => Some(NameChange::Removed(name)),
+		_ => None,
+	}
+}
+
+/// Case-folded name followed by the name itself, names only differing in case get their own key
+fn key(name: &[u8]) -> Vec<u8> {
+	let mut key = fold(name);
+	key.push(0);
+	key.extend_from_slice(name);
+	key
+}
+
+/// Lower case of UTF-8 names, ASCII lower case of other bytes
+fn fold(bytes: &[u8]) -> Vec<u8> {
+	match std::str::from_utf8(bytes) {
+		Ok(text) => text.to_lowercase().into_bytes(),
+		Err(_) => bytes.to_ascii_lowercase(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use starlog_runtime::AccountId;
+
+	fn put(db: &Database, names: &[&[u8]]) {
+		let mut transaction = DBTransaction::new();
+		for name in names {
+			transaction.put(COLUMN_NAMES, &key(name), name);
+		}
+		db.write(transaction).unwrap();
+	}
+
+	fn found(db: &Database, prefix: &[u8]) -> Vec<Vec<u8>> {
+		let mut names = Vec::new();
+		search(db, prefix, |name| {
+			names.push(name);
+			true
+		});
+		names
+	}
+
+	#[test]
+	fn search_stops_at_the_end_of_the_prefix() {
+		let path = std::env::temp_dir().join(format!("starlog-name-index-{}", std::process::id()));
+		{
+			let config = DatabaseConfig::with_columns(Some(NUM_COLUMNS));
+			let db = Database::open(&config, path.to_str().unwrap()).unwrap();
+			put(&db, &[b"Alice", b"alpha", b"bob", b"carol"]);
+
+			assert_eq!(found(&db, b"AL"), vec![b"Alice".to_vec(), b"alpha".to_vec()]);
+			assert_eq!(found(&db, b"b"), vec![b"bob".to_vec()]);
+			// names sharing no prefix with the search aren't returned
+			assert!(found(&db, b"d").is_empty());
+			assert!(found(&db, b"bobby").is_empty());
+		}
+		std::fs::remove_dir_all(&path).unwrap();
+	}
+
+	#[test]
+	fn released_names_are_removed() {
+		let owner = AccountId::default();
+		let released = MetalogEvent::NameReleased(owner.clone(), vec![1], b"name".to_vec(), 0);
+		assert_eq!(name_change(Event::metalog(released)), Some(NameChange::Removed(b"name".to_vec())));
+		let deleted = MetalogEvent::DeleteRequested(owner, vec![1], 5);
+		assert_eq!(name_change(Event::metalog(deleted)), None);
+	}
+}
@@ -0,0 +1,333 @@
+//! Availability probe of recently stored DIDs, run by full nodes with a reporting key.
+//!
+//! Offchain workers of this Substrate version can neither send HTTP requests nor sign transactions,
+//! so the probe runs in the node instead. It collects the DIDs stored in new best blocks and,
+//! a few blocks later, requests their file hash as CID with HTTP HEAD from a local IPFS gateway.
+//! Available content is attested, if the reporting account is an active pinning provider,
+//! otherwise nothing is submitted for it. Unavailable content is reported.
+//! Both are submitted as signed transactions to the local pool, if the DID is still stored with
+//! the probed file hash at the best block, e.g. it hasn't been stored in a retracted block only.
+//! Probes are spawned as tasks of their own, so they don't hold up the following import notifications.
+
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use futures::{future, Future, Stream};
+use hyper::{client::HttpConnector, Body, Client as HttpClient, Request};
+use log::{debug, info, warn};
+use parity_codec::{Compact, Decode, Encode};
+use parking_lot::Mutex;
+use primitives::{blake2_256, sr25519, storage::StorageKey, Blake2Hasher, Pair};
+use runtime_primitives::generic::Era;
+use starlog_runtime::{
+	availability::Call as AvailabilityCall,
+	metalog::RawEvent as MetalogEvent,
+	opaque::{self, Block, BlockId},
+	Address, AvailabilityApi, BlockNumber, Call, Event, Hash, MetalogApi, UncheckedExtrinsic,
+};
+use substrate_client::{
+	backend::Backend, runtime_api::ProvideRuntimeApi, BlockchainEvents, CallExecutor, Client,
+};
+use tokio::{runtime::TaskExecutor, timer::Timeout};
+use transaction_pool::txpool::{ChainApi as PoolApi, Pool};
+
+use crate::events::events_at;
+
+/// Blocks between storing a DID and probing it, so the content can propagate to the gateway
+const PROBE_DELAY: BlockNumber = 10;
+/// DIDs probed per block, the others wait for the next blocks
+const MAX_PROBES_PER_BLOCK: usize = 20;
+/// DIDs waiting to be probed, the oldest are dropped first
+const MAX_PENDING: usize = 1000;
+/// Attestations are renewed by probing again, one week with 6 seconds blocktime
+const ATTESTATION_PERIOD: BlockNumber = 100800;
+
+/// Gateway of a local IPFS daemon
+pub const DEFAULT_GATEWAY: &str = "http://127.0.0.1:8080";
+/// Swarm address of a local IPFS daemon
+pub const DEFAULT_LOCATION: &str = "/ip4/127.0.0.1/tcp/4001";
+/// Time the gateway gets to find the content
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Result of a HEAD request
+#[derive(Debug, PartialEq)]
+pub enum Probe {
+	Available,
+	Unavailable,
+}
+
+/// Local IPFS gateway
+pub struct Gateway {
+	url: String,
+	timeout: Duration,
+	client: HttpClient<HttpConnector>,
+}
+
+impl Gateway {
+	/// Gateway at the URL, e.g. http://127.0.0.1:8080
+	pub fn new(url: &str, timeout: Duration) -> Self {
+		Gateway {
+			url: url.trim_end_matches('/').to_owned(),
+			timeout,
+			client: HttpClient::new(),
+		}
+	}
+
+	/// Requests the CID, content the gateway doesn't find in time is unavailable.
+	/// Fails if the gateway itself can't be reached.
+	pub fn probe(&self, cid: &str) -> impl Future<Item = Probe, Error = String> {
+		let request = Request::head(format!("{}/ipfs/{}", self.url, cid))
+			.body(Body::empty())
+			.map_err(|e| e.to_string());
+		let client = self.client.clone();
+		let timeout = self.timeout;
+		future::result(request).and_then(move |request| {
+			Timeout::new(client.request(request), timeout).then(|result| match result {
+				Ok(response) if response.status().is_success() => Ok(Probe::Available),
+				Ok(_) => Ok(Probe::Unavailable),
+				Err(ref e) if e.is_elapsed() => Ok(Probe::Unavailable),
+				Err(e) => Err(format!("{:?}", e)),
+			})
+		})
+	}
+}
+
+/// DID waiting to be probed
+struct Pending {
+	stored_at: BlockNumber,
+	did: Vec<u8>,
+	cid: String,
+}
+
+/// Availability prober, submitting its results with the reporting key
+pub struct Prober<B, E, RA, A: PoolApi> {
+	client: Arc<Client<B, E, Block, RA>>,
+	pool: Arc<Pool<A>>,
+	gateway: Gateway,
+	key: sr25519::Pair,
+	/// Multiaddr attested as location of available content
+	location: Vec<u8>,
+	pending: Mutex<VecDeque<Pending>>,
+	/// Next nonce, if a transaction has been submitted before
+	nonce: Mutex<Option<u64>>,
+}
+
+impl<B, E, RA, A> Prober<B, E, RA, A> where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+	A: PoolApi<Block = Block> + 'static,
+	Client<B, E, Block, RA>: ProvideRuntimeApi,
+	<Client<B, E, Block, RA> as ProvideRuntimeApi>::Api: AvailabilityApi<Block> + MetalogApi<Block>,
+{
+	/// Create new prober, attesting available content at the location
+	pub fn new(
+		client: Arc<Client<B, E, Block, RA>>,
+		pool: Arc<Pool<A>>,
+		gateway: Gateway,
+		key: sr25519::Pair,
+		location: Vec<u8>,
+	) -> Self {
+		Prober {
+			client,
+			pool,
+			gateway,
+			key,
+			location,
+			pending: Mutex::new(VecDeque::new()),
+			nonce: Mutex::new(None),
+		}
+	}
+
+	/// Probes the DIDs stored in new best blocks on the executor until the node exits
+	pub fn follow(self: Arc<Self>, executor: TaskExecutor) -> impl Future<Item = (), Error = ()> {
+		self.client.import_notification_stream().for_each(move |notification| {
+			if !notification.is_new_best {
+				return Ok(());
+			}
+			let number = notification.header.number;
+			self.collect(notification.hash, number);
+
+			for pending in self.due(number) {
+				let prober = self.clone();
+				executor.spawn(self.gateway.probe(&pending.cid).then(move |result| {
+					match result {
+						Ok(probe) => prober.report(pending, probe, number),
+						Err(e) => warn!("Unable to reach the IPFS gateway: {}", e),
+					}
+					Ok(())
+				}));
+			}
+			Ok(())
+		})
+	}
+
+	/// Queues the DIDs stored in the block
+	fn collect(&self, hash: Hash, number: BlockNumber) {
+		let records = match events_at(&self.client, hash) {
+			Ok(records) => records,
+			Err(e) => return warn!("Unable to read the events of block {}: {}", hash, e),
+		};
+		let mut pending = self.pending.lock();
+		for record in records {
+			if let Event::metalog(MetalogEvent::Stored(_, _, did, file_hash, _, _, _)) = record.event {
+				match String::from_utf8(file_hash) {
+					Ok(cid) => pending.push_back(Pending { stored_at: number, did, cid }),
+					Err(_) => debug!("Skipping DID {:?}, its file hash is no CID", did),
+				}
+			}
+		}
+		while pending.len() > MAX_PENDING {
+			pending.pop_front();
+		}
+	}
+
+	/// Takes the DIDs, which have waited for the probe delay
+	fn due(&self, number: BlockNumber) -> Vec<Pending> {
+		let mut pending = self.pending.lock();
+		let mut due = Vec::new();
+		while due.len() < MAX_PROBES_PER_BLOCK
+			&& pending.front().map_or(false, |next| next.stored_at + PROBE_DELAY <= number)
+		{
+			due.extend(pending.pop_front());
+		}
+		due
+	}
+
+	/// Submits an attestation or an unavailability report
+	fn report(&self, pending: Pending, probe: Probe, number: BlockNumber) {
+		match self.is_stored(&pending) {
+			Ok(true) => {}
+			// reports cost a fee, DIDs deleted or stored in retracted blocks aren't reported
+			Ok(false) => return debug!("Skipping DID {:?}, it isn't stored at the best block", pending.did),
+			Err(e) => return warn!("Unable to look up DID {:?}: {}", pending.did, e),
+		}
+		let call = match probe {
+			Probe::Available => match self.is_provider() {
+				Ok(true) => AvailabilityCall::attest(
+					pending.did.clone(),
+					pending.cid.into_bytes(),
+					self.location.clone(),
+					number + ATTESTATION_PERIOD,
+				),
+				// only active staked providers can attest
+				Ok(false) => return,
+				Err(e) => return warn!("Unable to check the provider registration: {}", e),
+			},
+			Probe::Unavailable => AvailabilityCall::report_unavailable(pending.did.clone()),
+		};
+		match self.submit(Call::Availability(call)) {
+			Ok(()) => info!("Reported DID {:?} as {:?}", pending.did, probe),
+			Err(e) => warn!("Unable to report DID {:?} as {:?}: {}", pending.did, probe, e),
+		}
+	}
+
+	/// Whether the DID is stored with the probed file hash at the best block
+	fn is_stored(&self, pending: &Pending) -> Result<bool, String> {
+		let best = self.client.info().map_err(|e| format!("{:?}", e))?.chain.best_hash;
+		let metalog = self.client.runtime_api()
+			.metalog_by_did(&BlockId::hash(best), pending.did.clone())
+			.map_err(|e| format!("{:?}", e))?;
+		Ok(metalog.map_or(false, |metalog| metalog.file_hash == pending.cid.as_bytes()))
+	}
+
+	fn is_provider(&self) -> Result<bool, String> {
+		let best = self.client.info().map_err(|e| format!("{:?}", e))?.chain.best_hash;
+		self.client.runtime_api()
+			.is_provider(&BlockId::hash(best), self.key.public())
+			.map_err(|e| format!("{:?}", e))
+	}
+
+	/// Signs the call with the reporting key and submits it to the transaction pool
+	fn submit(&self, call: Call) -> Result<(), String> {
+		let best = self.client.info().map_err(|e| format!("{:?}", e))?.chain.best_hash;
+		let genesis = self.client.block_hash(0)
+			.map_err(|e| format!("{:?}", e))?
+			.ok_or_else(|| "Unknown genesis block".to_owned())?;
+
+		let mut nonce = self.nonce.lock();
+		let account_nonce = self.account_nonce(best)?;
+		let index = match *nonce {
+			// the transactions submitted before are still waiting in the pool
+			Some(next) if self.has_ready_transaction() => next.max(account_nonce),
+			// otherwise they have been included or dropped, e.g. because they were invalid
+			_ => account_nonce,
+		};
+		let payload = (Compact(index), call.clone(), Era::Immortal, genesis);
+		let signature = payload.using_encoded(|payload| if payload.len() > 256 {
+			self.key.sign(&blake2_256(payload)[..])
+		} else {
+			self.key.sign(payload)
+		});
+		let extrinsic = UncheckedExtrinsic::new_signed(index, call, Address::from(self.key.public()), signature, Era::Immortal);
+		let extrinsic = opaque::UncheckedExtrinsic::decode(&mut &extrinsic.encode()[..])
+			.ok_or_else(|| "Unable to encode the transaction".to_owned())?;
+
+		self.pool.submit_one(&BlockId::hash(best), extrinsic).map_err(|e| format!("{:?}", e))?;
+		*nonce = Some(index + 1);
+		Ok(())
+	}
+
+	/// Whether the pool holds a ready transaction signed with the reporting key
+	fn has_ready_transaction(&self) -> bool {
+		let sender = Address::from(self.key.public());
+		self.pool.ready().any(|transaction| {
+			UncheckedExtrinsic::decode(&mut &transaction.data.encode()[..])
+				.and_then(|extrinsic| extrinsic.signature)
+				.map_or(false, |(address, _, _, _)| address == sender)
+		})
+	}
+
+	/// Nonce of the reporting account at the block
+	fn account_nonce(&self, hash: Hash) -> Result<u64, String> {
+		let mut key = b"System AccountNonce".to_vec();
+		self.key.public().encode_to(&mut key);
+		let key = StorageKey(blake2_256(&key).to_vec());
+		match self.client.storage(&BlockId::hash(hash), &key).map_err(|e| format!("{:?}", e))? {
+			Some(data) => u64::decode(&mut &data.0[..]).ok_or_else(|| "Unable to decode the nonce".into()),
+			None => Ok(0),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::time::Instant;
+
+	use hyper::{service::service_fn, Method, Response, Server, StatusCode};
+	use tokio::{runtime::Runtime, timer::Delay};
+
+	/// Stub gateway, which knows one CID and never answers for another one
+	fn start_stub(runtime: &mut Runtime) -> String {
+		let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(|| service_fn(|request: Request<Body>| {
+			assert_eq!(request.method(), &Method::HEAD);
+			let status = match request.uri().path() {
+				"/ipfs/QmAvailable" => StatusCode::OK,
+				"/ipfs/QmSlow" => StatusCode::OK,
+				_ => StatusCode::NOT_FOUND,
+			};
+			let delay = if request.uri().path() == "/ipfs/QmSlow" { 5 } else { 0 };
+			Delay::new(Instant::now() + Duration::from_secs(delay)).then(move |_| {
+				Response::builder().status(status).body(Body::empty())
+			})
+		}));
+		let url = format!("http://{}/", server.local_addr());
+		runtime.spawn(server.map_err(|_| ()));
+		url
+	}
+
+	#[test]
+	fn probe_works() {
+		let mut runtime = Runtime::new().unwrap();
+		let gateway = Gateway::new(&start_stub(&mut runtime), Duration::from_millis(500));
+
+		assert_eq!(runtime.block_on(gateway.probe("QmAvailable")), Ok(Probe::Available));
+		assert_eq!(runtime.block_on(gateway.probe("QmMissing")), Ok(Probe::Unavailable));
+		assert_eq!(runtime.block_on(gateway.probe("QmSlow")), Ok(Probe::Unavailable));
+
+		// an unreachable gateway doesn't say anything about the content
+		let closed = Gateway::new("http://127.0.0.1:1", Duration::from_millis(500));
+		assert!(runtime.block_on(closed.probe("QmAvailable")).is_err());
+	}
+}
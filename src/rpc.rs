@@ -0,0 +1,293 @@
+//! Metalog JSON-RPC methods, served by full nodes next to the default RPC endpoints.
+//!
+//! DIDs and unique names are returned as UTF-8 strings, if they are readable text,
+//! otherwise as 0x-prefixed hex. Parameters are read the same way.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use jsonrpc_core::{Error, ErrorCode, IoHandler, Result};
+use jsonrpc_derive::rpc;
+use jsonrpc_http_server::{AccessControlAllowOrigin, DomainsValidation, Server, ServerBuilder};
+use primitives::{crypto::Ss58Codec, Blake2Hasher};
+use rustc_hex::{FromHex, ToHex};
+use serde_derive::Serialize;
+use starlog_runtime::{
+	opaque::{Block, BlockId},
+	AccountId, MetalogApi, MetalogRecord,
+};
+use substrate_client::{backend::Backend, runtime_api::ProvideRuntimeApi, CallExecutor, Client};
+
+use crate::name_index::NameIndex;
+
+/// Maximum number of metalogs returned by a name search
+const MAX_SEARCH_LIMIT: u64 = 100;
+
+/// Metalog in a JSON-friendly form
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetalogJson {
+	/// DID
+	pub did: String,
+	/// Unique name, none for the default name
+	pub unique_name: Option<String>,
+	/// License code
+	pub license_code: u16,
+	/// File hash as hex
+	pub file_hash: String,
+	/// Price as decimal string, balances don't fit into JSON numbers
+	pub price: String,
+	/// Metadata hash as hex
+	pub meta_hash: String,
+	/// ID of the pinning provider
+	pub provider: Option<u64>,
+	/// SS58 address of the original owner
+	pub creator: String,
+	/// Royalty of the creator in per mill
+	pub royalty: u16,
+	/// Timestamp
+	pub time: u64,
+	/// SS58 address of the owner
+	pub owner: Option<String>,
+}
+
+impl MetalogJson {
+	fn new(metalog: MetalogRecord, owner: Option<AccountId>) -> Self {
+		let unique_name = if metalog.unique_name.is_empty() || metalog.unique_name == vec![0] {
+			None
+		} else {
+			Some(to_text(&metalog.unique_name))
+		};
+		MetalogJson {
+			did: to_text(&metalog.did),
+			unique_name,
+			license_code: metalog.license_code,
+			file_hash: to_hex(&metalog.file_hash),
+			price: metalog.price.to_string(),
+			meta_hash: to_hex(&metalog.meta_hash),
+			provider: metalog.provider,
+			creator: metalog.creator.to_ss58check(),
+			royalty: metalog.royalty,
+			time: metalog.time,
+			owner: owner.map(|owner| owner.to_ss58check()),
+		}
+	}
+}
+
+/// Metalog queries, answered at the best block
+#[rpc]
+pub trait MetalogRpc {
+	/// Metalog of a DID
+	#[rpc(name = "metalog_getByDid")]
+	fn get_by_did(&self, did: String) -> Result<Option<MetalogJson>>;
+
+	/// Metalog a unique name points to
+	#[rpc(name = "metalog_getByName")]
+	fn get_by_name(&self, unique_name: String) -> Result<Option<MetalogJson>>;
+
+	/// Page of the metalogs of an SS58 address
+	#[rpc(name = "metalog_listByOwner")]
+	fn list_by_owner(&self, owner: String, start: u64, limit: u64) -> Result<Vec<MetalogJson>>;
+
+	/// Fee of a unique name as decimal string, none if it can't be bought directly
+	#[rpc(name = "metalog_quoteName")]
+	fn quote_name(&self, unique_name: String) -> Result<Option<String>>;
+
+	/// Metalogs of unique names starting with the prefix, regardless of case
+	#[rpc(name = "metalog_searchNames")]
+	fn search_names(&self, prefix: String, limit: u64) -> Result<Vec<MetalogJson>>;
+}
+
+/// Metalog RPC backed by the metalog runtime API
+pub struct Metalog<B, E, RA> {
+	client: Arc<Client<B, E, Block, RA>>,
+	names: Arc<NameIndex<B, E, RA>>,
+}
+
+impl<B, E, RA> Metalog<B, E, RA> where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+{
+	/// Create new metalog RPC handler
+	pub fn new(client: Arc<Client<B, E, Block, RA>>, names: Arc<NameIndex<B, E, RA>>) -> Self {
+		Metalog { client, names }
+	}
+
+	fn best_block(&self) -> Result<BlockId> {
+		let info = self.client.info().map_err(client_error)?;
+		Ok(BlockId::hash(info.chain.best_hash))
+	}
+}
+
+impl<B, E, RA> MetalogRpc for Metalog<B, E, RA> where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+	Client<B, E, Block, RA>: ProvideRuntimeApi,
+	<Client<B, E, Block, RA> as ProvideRuntimeApi>::Api: MetalogApi<Block>,
+{
+	fn get_by_did(&self, did: String) -> Result<Option<MetalogJson>> {
+		let at = self.best_block()?;
+		let api = self.client.runtime_api();
+		let did = from_text(did)?;
+
+		let metalog = api.metalog_by_did(&at, did.clone()).map_err(client_error)?;
+		let owner = api.owner_of(&at, did).map_err(client_error)?;
+		Ok(metalog.map(|metalog| MetalogJson::new(metalog, owner)))
+	}
+
+	fn get_by_name(&self, unique_name: String) -> Result<Option<MetalogJson>> {
+		let at = self.best_block()?;
+		let api = self.client.runtime_api();
+
+		match api.metalog_by_name(&at, from_text(unique_name)?).map_err(client_error)? {
+			Some(metalog) => {
+				let owner = api.owner_of(&at, metalog.did.clone()).map_err(client_error)?;
+				Ok(Some(MetalogJson::new(metalog, owner)))
+			}
+			None => Ok(None),
+		}
+	}
+
+	fn list_by_owner(&self, owner: String, start: u64, limit: u64) -> Result<Vec<MetalogJson>> {
+		let at = self.best_block()?;
+		let owner = AccountId::from_ss58check(&owner)
+			.map_err(|_| Error::invalid_params("owner is no valid SS58 address"))?;
+
+		let metalogs = self.client.runtime_api()
+			.metalogs_of_owner(&at, owner.clone(), start, limit)
+			.map_err(client_error)?;
+		Ok(metalogs.into_iter().map(|metalog| MetalogJson::new(metalog, Some(owner.clone()))).collect())
+	}
+
+	fn quote_name(&self, unique_name: String) -> Result<Option<String>> {
+		let at = self.best_block()?;
+		let price = self.client.runtime_api()
+			.name_price(&at, from_text(unique_name)?)
+			.map_err(client_error)?;
+		Ok(price.map(|price| price.to_string()))
+	}
+
+	fn search_names(&self, prefix: String, limit: u64) -> Result<Vec<MetalogJson>> {
+		let at = self.best_block()?;
+		let api = self.client.runtime_api();
+		let prefix = from_text(prefix)?;
+		let limit = limit.min(MAX_SEARCH_LIMIT) as usize;
+		if limit == 0 {
+			return Ok(Vec::new());
+		}
+
+		let mut metalogs = Vec::new();
+		let mut error = None;
+		self.names.search(&prefix, |name| {
+			// names of retracted blocks and names without a DID are skipped
+			match api.metalog_by_name(&at, name) {
+				Ok(Some(metalog)) => match api.owner_of(&at, metalog.did.clone()) {
+					Ok(owner) => metalogs.push(MetalogJson::new(metalog, owner)),
+					Err(e) => error = Some(client_error(e)),
+				},
+				Ok(None) => {}
+				Err(e) => error = Some(client_error(e)),
+			}
+			error.is_none() && metalogs.len() < limit
+		});
+
+		match error {
+			Some(error) => Err(error),
+			None => Ok(metalogs),
+		}
+	}
+}
+
+/// Starts the HTTP server for the metalog RPC, it stops once the server is dropped
+/// Without allowed origins, no CORS headers are sent and browsers only accept responses for the node's own origin
+pub fn start_http<B, E, RA>(
+	addr: &SocketAddr,
+	cors: Option<Vec<String>>,
+	client: Arc<Client<B, E, Block, RA>>,
+	names: Arc<NameIndex<B, E, RA>>,
+) -> std::io::Result<Server> where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+	Client<B, E, Block, RA>: ProvideRuntimeApi,
+	<Client<B, E, Block, RA> as ProvideRuntimeApi>::Api: MetalogApi<Block>,
+{
+	let mut io = IoHandler::new();
+	io.extend_with(Metalog::new(client, names).to_delegate());
+	serve(io, addr, cors)
+}
+
+/// Serves the handler over HTTP, only the allowed origins get CORS headers
+fn serve(io: IoHandler, addr: &SocketAddr, cors: Option<Vec<String>>) -> std::io::Result<Server> {
+	// an empty list rejects every cross-origin request, disabling the validation would allow all of them
+	let origins = cors.unwrap_or_default().into_iter().map(AccessControlAllowOrigin::from).collect();
+	ServerBuilder::new(io)
+		.threads(1)
+		.cors(DomainsValidation::AllowOnly(origins))
+		.start_http(addr)
+}
+
+/// Readable text as is, other bytes as 0x-prefixed hex
+fn to_text(bytes: &[u8]) -> String {
+	match std::str::from_utf8(bytes) {
+		Ok(text) if !text.starts_with("0x") && !text.chars().any(char::is_control) => text.to_owned(),
+		_ => to_hex(bytes),
+	}
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+	format!("0x{}", bytes.to_hex::<String>())
+}
+
+/// Parameter bytes, 0x-prefixed strings are read as hex
+fn from_text(text: String) -> Result<Vec<u8>> {
+	if text.starts_with("0x") {
+		return text[2..].from_hex().map_err(|_| Error::invalid_params("invalid hex string"));
+	}
+	Ok(text.into_bytes())
+}
+
+fn client_error<T: std::fmt::Debug>(error: T) -> Error {
+	Error {
+		code: ErrorCode::ServerError(1),
+		message: format!("{:?}", error),
+		data: None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::io::{Read, Write};
+	use std::net::TcpStream;
+
+	/// Response headers of a request with the origin, in lower case
+	fn response_headers(server: &Server, origin: &str) -> String {
+		let body = r#"{"jsonrpc":"2.0","id":1,"method":"unknown","params":[]}"#;
+		let mut stream = TcpStream::connect(server.address()).unwrap();
+		write!(
+			stream,
+			"POST / HTTP/1.1\r\nHost: localhost\r\nOrigin: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+			origin,
+			body.len(),
+			body,
+		).unwrap();
+		let mut response = String::new();
+		stream.read_to_string(&mut response).unwrap();
+		response.split("\r\n\r\n").next().unwrap().to_lowercase()
+	}
+
+	#[test]
+	fn foreign_origins_get_no_cors_headers() {
+		let addr = "127.0.0.1:0".parse().unwrap();
+		let server = serve(IoHandler::new(), &addr, None).unwrap();
+		assert!(!response_headers(&server, "https://example.com").contains("access-control-allow-origin"));
+
+		let allowed = vec!["https://dweb.page".to_owned()];
+		let server = serve(IoHandler::new(), &addr, Some(allowed)).unwrap();
+		assert!(!response_headers(&server, "https://example.com").contains("access-control-allow-origin"));
+		assert!(response_headers(&server, "https://dweb.page").contains("access-control-allow-origin: https://dweb.page"));
+	}
+}
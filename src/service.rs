@@ -2,7 +2,11 @@
 
 #![warn(unused_extern_crates)]
 
+use std::env;
+use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::Arc;
+use futures::Future;
 use log::info;
 use transaction_pool::{self, txpool::{Pool as TransactionPool}};
 use starlog_runtime::{self, GenesisConfig, opaque::Block, RuntimeApi};
@@ -14,12 +18,16 @@ use substrate_service::{
 use basic_authorship::ProposerFactory;
 use consensus::{import_queue, start_aura, AuraImportQueue, SlotDuration, NothingExtra};
 use substrate_client as client;
-use primitives::{ed25519::Pair, Pair as PairT};
+use primitives::{ed25519::Pair, sr25519, Pair as PairT};
 use inherents::InherentDataProviders;
 use network::construct_simple_protocol;
 use substrate_executor::native_executor_instance;
 use substrate_service::construct_service_factory;
 
+use crate::indexer::Indexer;
+use crate::name_index::NameIndex;
+use crate::probe::{self, Gateway, Prober};
+
 pub use substrate_executor::NativeExecutor;
 // Our native executor instance.
 native_executor_instance!(
@@ -57,6 +65,54 @@ construct_service_factory! {
 			},
 		AuthoritySetup = {
 			|service: Self::FullService, executor: TaskExecutor, key: Option<Arc<Pair>>| {
+				// metalog and federation events are indexed into SQLite next to the chain database
+				let index_path = Path::new(&service.config.database_path).with_file_name("index.sqlite");
+				let indexer = Arc::new(Indexer::open(&index_path, service.client())
+					.map_err(|e| format!("Unable to open the event index: {}", e))?);
+				executor.spawn(indexer.follow().select(service.on_exit()).map(|_| ()).map_err(|_| ()));
+
+				// stored DIDs are probed, if the secret URI of a reporting account is set
+				if let Ok(seed) = env::var("STARLOG_PROBE_SEED") {
+					let key = sr25519::Pair::from_string(&seed, None)
+						.map_err(|e| format!("Invalid STARLOG_PROBE_SEED: {:?}", e))?;
+					let gateway_url = env::var("STARLOG_IPFS_GATEWAY").unwrap_or_else(|_| probe::DEFAULT_GATEWAY.into());
+					let location = env::var("STARLOG_PROBE_LOCATION").unwrap_or_else(|_| probe::DEFAULT_LOCATION.into());
+					let gateway = Gateway::new(&gateway_url, probe::PROBE_TIMEOUT);
+					let prober = Arc::new(Prober::new(
+						service.client(),
+						service.transaction_pool(),
+						gateway,
+						key,
+						location.into_bytes(),
+					));
+					info!("Probing stored DIDs with the IPFS gateway {}", gateway_url);
+					executor.spawn(prober.follow(executor.clone()).select(service.on_exit()).map(|_| ()).map_err(|_| ()));
+				}
+
+				if let Some(rpc_http) = service.config.rpc_http {
+					// the name search index is kept next to the chain database
+					let names_path = Path::new(&service.config.database_path).with_file_name("names");
+					let names = Arc::new(NameIndex::open(&names_path, service.client())
+						.map_err(|e| format!("Unable to open the name index: {}", e))?);
+					executor.spawn(names.clone().follow().select(service.on_exit()).map(|_| ()).map_err(|_| ()));
+
+					// metalog methods are served on STARLOG_RPC_PORT, by default on the port after the default HTTP RPC
+					let port = match env::var("STARLOG_RPC_PORT") {
+						Ok(port) => port.parse().map_err(|e| format!("Invalid STARLOG_RPC_PORT: {}", e))?,
+						Err(_) => rpc_http.port().checked_add(1).ok_or_else(|| format!(
+							"The HTTP RPC port {} has no next port for the metalog RPC, set STARLOG_RPC_PORT", rpc_http.port()
+						))?,
+					};
+					let addr = SocketAddr::new(rpc_http.ip(), port);
+					// comma-separated origins allowed to call the metalog RPC from a browser
+					let cors = env::var("STARLOG_RPC_CORS").ok()
+						.map(|origins| origins.split(',').map(|origin| origin.trim().to_owned()).collect());
+					let server = crate::rpc::start_http(&addr, cors, service.client(), names)
+						.map_err(|e| format!("Unable to start metalog RPC on {}: {}", addr, e))?;
+					info!("Metalog RPC listening on {}", addr);
+					executor.spawn(service.on_exit().map(move |_| drop(server)));
+				}
+
 				if let Some(key) = key {
 					info!("Using authority key {}", key.public());
 					let proposer = Arc::new(ProposerFactory {